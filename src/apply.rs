@@ -0,0 +1,187 @@
+use crate::config::Config;
+use crate::convert;
+use crate::dep;
+use crate::error::{io_error, GrumpyError};
+use crate::manifest::{Dependency, Manifest};
+use crate::output;
+use crate::project::Project;
+use crate::spec::{Spec, SPEC_FILE};
+use crate::targets::Targets;
+use crate::transaction::Transaction;
+use crate::{create_binary_script, current_dir, offer_roll_back};
+use argh::FromArgs;
+use std::path::Path;
+
+#[derive(FromArgs, PartialEq, Debug)]
+/// add whatever grumpy.toml declares that the project doesn't have yet
+#[argh(subcommand, name = "apply")]
+pub struct ApplySubCommand {
+    /// name of project
+    #[argh(option, short = 'p')]
+    project_name: Option<String>,
+
+    /// path to the project's Cargo.toml
+    #[argh(option)]
+    manifest_path: Option<String>,
+
+    /// spec file to apply, defaults to grumpy.toml at the root of the package
+    #[argh(option)]
+    spec: Option<String>,
+}
+
+/// Sets the `[package]` fields the spec gives. Fields the package inherits from its workspace are
+/// left to the workspace.
+fn apply_package(
+    transaction: &mut Transaction,
+    project_root: &Path,
+    spec: &Spec,
+) -> Result<(), GrumpyError> {
+    let mut manifest = Manifest::load(transaction, project_root)?;
+
+    for (key, value) in &spec.package {
+        if manifest.inherits(key) {
+            output::note(&format!(
+                "Not setting package.{}, it's inherited from the workspace",
+                key
+            ));
+        } else {
+            manifest.set_package_value(key, value)?;
+        }
+    }
+
+    manifest.save(transaction)
+}
+
+/// Creates the binaries the project doesn't have yet. Existing binaries are left as they are, even
+/// if they were made from another template or layout.
+fn apply_bins(
+    transaction: &mut Transaction,
+    config: &Config,
+    project_root: &Path,
+    targets: &Targets,
+    spec: &Spec,
+) -> Result<(), GrumpyError> {
+    for bin in &spec.bins {
+        if targets.bin(&bin.name).is_none() {
+            create_binary_script(
+                transaction,
+                config,
+                project_root,
+                &bin.name,
+                bin.template.as_ref(),
+                bin.layout,
+                false,
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+/// The profile dependencies the project doesn't have yet. One it already depends on, whatever
+/// the version or however it's written, is left as it is.
+fn missing_dependencies(
+    transaction: &Transaction,
+    project_root: &Path,
+    dependencies: Vec<Dependency>,
+) -> Result<Vec<Dependency>, GrumpyError> {
+    let manifest = Manifest::load(transaction, project_root)?;
+
+    Ok(dependencies
+        .into_iter()
+        .filter(|dependency| {
+            !manifest
+                .dependency_names(dependency.kind)
+                .contains(&dependency.name)
+        })
+        .collect())
+}
+
+/// Writes the files the project doesn't have yet. A file that's there already belongs to the
+/// project, so is only reported if it has drifted from the spec.
+fn apply_files(
+    transaction: &mut Transaction,
+    project_root: &Path,
+    spec: &Spec,
+) -> Result<(), GrumpyError> {
+    for file in &spec.files {
+        let path = project_root.join(&file.path);
+
+        if !transaction.exists(&path) {
+            transaction
+                .write(&path, file.contents.as_bytes())
+                .map_err(io_error("write", &path))?;
+        } else if !transaction
+            .read(&path)
+            .is_ok_and(|contents| contents == file.contents.as_bytes())
+        {
+            output::note(&format!(
+                "Leaving {}, which differs from the spec",
+                path.display()
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+pub fn process_apply(apply_args: &ApplySubCommand, dry_run: bool) -> Result<(), GrumpyError> {
+    let project = Project::locate(
+        apply_args.project_name.as_ref(),
+        apply_args.manifest_path.as_ref(),
+    )?;
+    let project_root = project.root;
+    let workspace_root = project.workspace_root;
+
+    let spec_path = match &apply_args.spec {
+        Some(spec_path) => current_dir()?.join(spec_path),
+        None => project_root.join(SPEC_FILE),
+    };
+
+    let spec = Spec::load(&spec_path)?;
+    let config = Config::load(Some(&project_root), workspace_root.as_deref())?;
+
+    // Look the profiles up first, so an unknown one stops everything before anything changes.
+    let dependencies = config.profile_dependencies(&spec.profiles)?;
+
+    let mut transaction = Transaction::new(dry_run);
+
+    // Before anything is planned, so a dry run sees the binaries through cargo just as a real run
+    // does. Converting to a library moves src/main.rs, but keeps the binary's name.
+    let targets = Targets::load(&transaction, &project_root)?;
+
+    apply_package(&mut transaction, &project_root, &spec)?;
+
+    if spec.lib {
+        convert::convert_to_lib(&mut transaction, &project_root)?;
+    }
+
+    apply_bins(&mut transaction, &config, &project_root, &targets, &spec)?;
+    apply_files(&mut transaction, &project_root, &spec)?;
+
+    let dependencies = missing_dependencies(&transaction, &project_root, dependencies)?;
+
+    if !dependencies.is_empty() {
+        if let Err(error) = dep::add_dependencies(
+            &mut transaction,
+            &project_root,
+            workspace_root.as_deref(),
+            &dependencies,
+        ) {
+            offer_roll_back(transaction);
+            return Err(error);
+        }
+    }
+
+    // A dry run says as much itself.
+    if !dry_run && !transaction.has_changes() {
+        output::note(&format!(
+            "Nothing to change, the project already matches {}",
+            spec_path.display()
+        ));
+    }
+
+    transaction.commit();
+
+    Ok(())
+}
@@ -0,0 +1,738 @@
+use crate::current_dir;
+use crate::dirs;
+use crate::error::{io_error, GrumpyError};
+use crate::manifest::Dependency;
+use crate::names;
+use crate::output::{self, Event};
+use crate::project::{self, Project};
+use crate::template;
+use argh::FromArgs;
+use std::collections::BTreeMap;
+use std::env;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use toml_edit::{Array, DocumentMut, Item};
+
+/// Name of the per-project configuration file, which lives in the project root.
+pub const PROJECT_CONFIG_FILE: &str = ".grumpy.toml";
+
+/// The type of a configuration key, along with its built-in default.
+enum Kind {
+    Str(Option<&'static str>),
+    Bool(bool),
+    List(&'static [&'static str]),
+}
+
+struct KeySpec {
+    key: &'static str,
+    env: &'static str,
+    kind: Kind,
+}
+
+/// Every key cargo-grumpy understands. Keys are written as `section.name` on the command line and
+/// as `name` inside a `[section]` table in the config files.
+const KEYS: &[KeySpec] = &[
+    KeySpec {
+        key: "new.script-name",
+        env: "CARGO_GRUMPY_NEW_SCRIPT_NAME",
+        kind: Kind::Str(Some("main.rs")),
+    },
+    KeySpec {
+        key: "new.project-files",
+        env: "CARGO_GRUMPY_NEW_PROJECT_FILES",
+        kind: Kind::Bool(false),
+    },
+    KeySpec {
+        key: "templates.default",
+        env: "CARGO_GRUMPY_TEMPLATES_DEFAULT",
+        kind: Kind::Str(Some(template::DEFAULT_TEMPLATE)),
+    },
+    KeySpec {
+        key: "templates.team-dir",
+        env: "CARGO_GRUMPY_TEAM_TEMPLATES",
+        kind: Kind::Str(None),
+    },
+    KeySpec {
+        key: "dependencies.bin-profiles",
+        env: "CARGO_GRUMPY_DEPENDENCIES_BIN_PROFILES",
+        kind: Kind::List(&["cli"]),
+    },
+    KeySpec {
+        key: "dependencies.lib-profiles",
+        env: "CARGO_GRUMPY_DEPENDENCIES_LIB_PROFILES",
+        kind: Kind::List(&["lib-minimal"]),
+    },
+];
+
+/// Dependency profiles are named sets of `name@version` crates, defined as `profiles.<name>`.
+/// Entries can be prefixed with `dev:` or `build:` to add them as dev or build dependencies.
+const PROFILE_PREFIX: &str = "profiles.";
+const PROFILE_ENV_PREFIX: &str = "CARGO_GRUMPY_PROFILE_";
+
+static PROFILE_SPEC: KeySpec = KeySpec {
+    key: "profiles.<name>",
+    env: "CARGO_GRUMPY_PROFILE_<NAME>",
+    kind: Kind::List(&[]),
+};
+
+/// Profiles available without any configuration. These can be replaced by defining a profile of
+/// the same name in a config file.
+const BUILTIN_PROFILES: &[(&str, &[&str])] = &[
+    (
+        "cli",
+        &[
+            "fehler@1.0",
+            "anyhow@1.0",
+            "thiserror@1.0",
+            "log@0.4",
+            "log4rs@1.1",
+        ],
+    ),
+    (
+        "service",
+        &[
+            "anyhow@1.0",
+            "thiserror@1.0",
+            "log@0.4",
+            "log4rs@1.1",
+            "serde@1.0",
+            "serde_json@1.0",
+        ],
+    ),
+    ("lib-minimal", &["thiserror@1.0", "log@0.4"]),
+];
+
+fn key_spec(key: &str) -> Option<&'static KeySpec> {
+    if key.starts_with(PROFILE_PREFIX) && key.len() > PROFILE_PREFIX.len() {
+        return Some(&PROFILE_SPEC);
+    }
+
+    KEYS.iter().find(|spec| spec.key == key)
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Str(String),
+    Bool(bool),
+    List(Vec<String>),
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Str(value) => write!(f, "{:?}", value),
+            Value::Bool(value) => write!(f, "{}", value),
+            Value::List(values) => {
+                let quoted: Vec<String> = values.iter().map(|v| format!("{:?}", v)).collect();
+                write!(f, "[{}]", quoted.join(", "))
+            }
+        }
+    }
+}
+
+/// Where the effective value of a key came from.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Source {
+    Default,
+    Global(PathBuf),
+    Project(PathBuf),
+    Env(String),
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Source::Default => write!(f, "default"),
+            Source::Global(path) => write!(f, "global config {}", path.display()),
+            Source::Project(path) => write!(f, "project config {}", path.display()),
+            Source::Env(name) => write!(f, "environment variable {}", name),
+        }
+    }
+}
+
+pub struct Setting {
+    pub value: Value,
+    pub source: Source,
+}
+
+/// The effective configuration, layered from built-in defaults, the global config file, the
+/// workspace's `.grumpy.toml`, the project's `.grumpy.toml` and finally environment variables.
+pub struct Config {
+    settings: BTreeMap<String, Setting>,
+}
+
+impl Config {
+    pub fn load(
+        project_root: Option<&Path>,
+        workspace_root: Option<&Path>,
+    ) -> Result<Self, GrumpyError> {
+        let mut settings = BTreeMap::new();
+
+        for spec in KEYS {
+            let value = match spec.kind {
+                Kind::Str(Some(default)) => Value::Str(default.to_string()),
+                Kind::Str(None) => continue,
+                Kind::Bool(default) => Value::Bool(default),
+                Kind::List(defaults) => {
+                    Value::List(defaults.iter().map(|d| d.to_string()).collect())
+                }
+            };
+
+            settings.insert(
+                spec.key.to_string(),
+                Setting {
+                    value,
+                    source: Source::Default,
+                },
+            );
+        }
+
+        for (name, dependencies) in BUILTIN_PROFILES {
+            settings.insert(
+                format!("{}{}", PROFILE_PREFIX, name),
+                Setting {
+                    value: Value::List(dependencies.iter().map(|d| d.to_string()).collect()),
+                    source: Source::Default,
+                },
+            );
+        }
+
+        let mut config = Config { settings };
+
+        if let Some(global_path) = global_config_path() {
+            config.apply_file(&global_path, Source::Global(global_path.clone()))?;
+        }
+
+        // A package that's its own workspace root only has the one file.
+        if let Some(workspace_root) = workspace_root.filter(|root| Some(*root) != project_root) {
+            let workspace_path = workspace_root.join(PROJECT_CONFIG_FILE);
+            config.apply_file(&workspace_path, Source::Project(workspace_path.clone()))?;
+        }
+
+        if let Some(project_root) = project_root {
+            let project_path = project_root.join(PROJECT_CONFIG_FILE);
+            config.apply_file(&project_path, Source::Project(project_path.clone()))?;
+        }
+
+        config.apply_env()?;
+
+        Ok(config)
+    }
+
+    fn apply_file(&mut self, path: &Path, source: Source) -> Result<(), GrumpyError> {
+        if !path.exists() {
+            return Ok(());
+        }
+
+        let location = source.to_string();
+        let invalid = |message: String| GrumpyError::InvalidConfig {
+            location: location.clone(),
+            message,
+        };
+
+        let document = read_document(path, &location)?;
+
+        for (section, table) in document.iter() {
+            let table = table
+                .as_table_like()
+                .ok_or_else(|| invalid(format!("Unexpected top-level key {:?}", section)))?;
+
+            for (name, item) in table.iter() {
+                let key = format!("{}.{}", section, name);
+
+                let spec =
+                    key_spec(&key).ok_or_else(|| invalid(format!("Unknown key {:?}", key)))?;
+
+                let value = value_from_item(spec, item)
+                    .ok_or_else(|| invalid(format!("Wrong type for key {:?}", key)))?;
+
+                self.set(&key, spec, value, source.clone())
+                    .map_err(invalid)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn apply_env(&mut self) -> Result<(), GrumpyError> {
+        let mut overrides: Vec<(String, &'static KeySpec, String)> = KEYS
+            .iter()
+            .map(|spec| (spec.key.to_string(), spec, spec.env.to_string()))
+            .collect();
+
+        // Profiles can't be listed up front, so pick up any CARGO_GRUMPY_PROFILE_<NAME> variable.
+        for (name, _) in env::vars() {
+            if let Some(profile) = name.strip_prefix(PROFILE_ENV_PREFIX) {
+                if !profile.is_empty() {
+                    let key = format!(
+                        "{}{}",
+                        PROFILE_PREFIX,
+                        profile.to_lowercase().replace('_', "-")
+                    );
+
+                    overrides.push((key, &PROFILE_SPEC, name.clone()));
+                }
+            }
+        }
+
+        for (key, spec, env_name) in overrides {
+            if let Ok(raw) = env::var(&env_name) {
+                let source = Source::Env(env_name);
+                let invalid = |message| GrumpyError::InvalidConfig {
+                    location: source.to_string(),
+                    message,
+                };
+
+                let value = parse_value(spec, &raw).map_err(invalid)?;
+
+                self.set(&key, spec, value, source.clone())
+                    .map_err(invalid)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn set(
+        &mut self,
+        key: &str,
+        spec: &KeySpec,
+        value: Value,
+        source: Source,
+    ) -> Result<(), String> {
+        validate(key, spec, &value)?;
+
+        self.settings
+            .insert(key.to_string(), Setting { value, source });
+
+        Ok(())
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Setting> {
+        self.settings.get(key)
+    }
+
+    pub fn get_str(&self, key: &str) -> Option<&str> {
+        match self.get(key).map(|setting| &setting.value) {
+            Some(Value::Str(value)) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn get_bool(&self, key: &str) -> bool {
+        match self.get(key).map(|setting| &setting.value) {
+            Some(Value::Bool(value)) => *value,
+            _ => false,
+        }
+    }
+
+    pub fn get_list(&self, key: &str) -> &[String] {
+        match self.get(key).map(|setting| &setting.value) {
+            Some(Value::List(values)) => values,
+            _ => &[],
+        }
+    }
+
+    /// Names of every dependency profile, built in or configured.
+    pub fn profile_names(&self) -> Vec<&str> {
+        self.settings
+            .keys()
+            .filter_map(|key| key.strip_prefix(PROFILE_PREFIX))
+            .collect()
+    }
+
+    /// Combines the dependencies of the named profiles. If several profiles ask for the same
+    /// crate, the version from the last of them wins.
+    pub fn profile_dependencies(
+        &self,
+        profiles: &[String],
+    ) -> Result<Vec<Dependency>, GrumpyError> {
+        let mut dependencies: Vec<Dependency> = vec![];
+
+        for profile in profiles {
+            let key = format!("{}{}", PROFILE_PREFIX, profile);
+
+            match self.get(&key).map(|setting| &setting.value) {
+                Some(Value::List(profile_dependencies)) => {
+                    for spec in profile_dependencies {
+                        let dependency = Dependency::parse(spec)?;
+
+                        dependencies.retain(|existing| {
+                            existing.name != dependency.name || existing.kind != dependency.kind
+                        });
+                        dependencies.push(dependency);
+                    }
+                }
+                _ => {
+                    return Err(GrumpyError::UnknownProfile {
+                        name: profile.clone(),
+                    })
+                }
+            }
+        }
+
+        Ok(dependencies)
+    }
+}
+
+/// Location of the global config file, in the user's cargo-grumpy config directory.
+pub fn global_config_path() -> Option<PathBuf> {
+    dirs::user_config_dir().map(|dir| dir.join("config.toml"))
+}
+
+fn read_document(path: &Path, location: &str) -> Result<DocumentMut, GrumpyError> {
+    fs::read_to_string(path)
+        .map_err(io_error("read", path))?
+        .parse::<DocumentMut>()
+        .map_err(|e| GrumpyError::InvalidConfig {
+            location: location.to_string(),
+            message: format!("Unable to parse TOML: {}", e),
+        })
+}
+
+fn value_from_item(spec: &KeySpec, item: &Item) -> Option<Value> {
+    match spec.kind {
+        Kind::Str(_) => item.as_str().map(|value| Value::Str(value.to_string())),
+        Kind::Bool(_) => item.as_bool().map(Value::Bool),
+        Kind::List(_) => {
+            let array = item.as_array()?;
+
+            let values: Option<Vec<String>> = array
+                .iter()
+                .map(|value| value.as_str().map(|value| value.to_string()))
+                .collect();
+
+            values.map(Value::List)
+        }
+    }
+}
+
+/// Parses a value given on the command line or in an environment variable. Lists are comma
+/// separated, and flags are `true` or `false`.
+fn parse_value(spec: &KeySpec, raw: &str) -> Result<Value, String> {
+    match spec.kind {
+        Kind::Str(_) => Ok(Value::Str(raw.to_string())),
+        Kind::Bool(_) => raw.trim().parse().map(Value::Bool).map_err(|_| {
+            format!(
+                "Invalid value {:?} for key {:?}, expected true or false",
+                raw, spec.key
+            )
+        }),
+        Kind::List(_) => Ok(Value::List(
+            raw.split(',')
+                .map(|value| value.trim())
+                .filter(|value| !value.is_empty())
+                .map(|value| value.to_string())
+                .collect(),
+        )),
+    }
+}
+
+fn validate(key: &str, spec: &KeySpec, value: &Value) -> Result<(), String> {
+    match (spec.key, value) {
+        ("new.script-name", Value::Str(name)) => {
+            names::script_name(name).map_err(|e| format!("{} for key {:?}", e, key))?;
+        }
+        ("templates.default", Value::Str(name))
+            if name.is_empty() || name.contains('/') || name.contains('\\') =>
+        {
+            return Err(format!("Invalid value {:?} for key {:?}", name, key));
+        }
+        ("profiles.<name>", Value::List(crates)) => {
+            for dependency in crates {
+                Dependency::parse(dependency).map_err(|e| format!("{} for key {:?}", e, key))?;
+            }
+        }
+        _ => {}
+    }
+
+    Ok(())
+}
+
+#[derive(FromArgs, PartialEq, Debug)]
+/// show or change cargo-grumpy configuration
+#[argh(subcommand, name = "config")]
+pub struct ConfigSubCommand {
+    #[argh(subcommand)]
+    action: ConfigAction,
+}
+
+#[derive(FromArgs, PartialEq, Debug)]
+#[argh(subcommand)]
+enum ConfigAction {
+    Show(ShowAction),
+    Get(GetAction),
+    Set(SetAction),
+}
+
+#[derive(FromArgs, PartialEq, Debug)]
+/// show every effective setting and where it came from
+#[argh(subcommand, name = "show")]
+struct ShowAction {}
+
+#[derive(FromArgs, PartialEq, Debug)]
+/// show the effective value of a single setting
+#[argh(subcommand, name = "get")]
+struct GetAction {
+    /// key to look up, such as new.script-name
+    #[argh(positional)]
+    key: String,
+}
+
+#[derive(FromArgs, PartialEq, Debug)]
+/// change a setting in the project or global config file
+#[argh(subcommand, name = "set")]
+struct SetAction {
+    /// key to change, such as new.script-name
+    #[argh(positional)]
+    key: String,
+
+    /// new value, with list entries separated by commas
+    #[argh(positional)]
+    value: String,
+
+    /// write to the global config file rather than the project's .grumpy.toml
+    #[argh(switch, short = 'g')]
+    global: bool,
+}
+
+pub fn process_config(config_args: &ConfigSubCommand) -> Result<(), GrumpyError> {
+    let (project_root, workspace_root) = match Project::locate(None, None) {
+        Ok(project) => (project.root, project.workspace_root),
+        // Outside a package, use the directory of the nearest Cargo.toml, which will be a
+        // workspace root, or failing that the current directory.
+        Err(GrumpyError::NoProjectName) | Err(GrumpyError::VirtualManifest { .. }) => {
+            let current_dir = current_dir()?;
+            (
+                project::find_root(&current_dir).unwrap_or(current_dir),
+                None,
+            )
+        }
+        Err(error) => return Err(error),
+    };
+
+    match &config_args.action {
+        ConfigAction::Show(_) => {
+            let config = Config::load(Some(&project_root), workspace_root.as_deref())?;
+
+            for spec in KEYS {
+                if config.get(spec.key).is_none() {
+                    output::emit(Event::ConfigSetting {
+                        key: spec.key,
+                        setting: None,
+                    });
+                }
+            }
+
+            for (key, setting) in &config.settings {
+                output::emit(Event::ConfigSetting {
+                    key,
+                    setting: Some(setting),
+                });
+            }
+        }
+        ConfigAction::Get(get_args) => {
+            if key_spec(&get_args.key).is_none() {
+                return Err(GrumpyError::UnknownConfigKey {
+                    key: get_args.key.clone(),
+                });
+            }
+
+            let config = Config::load(Some(&project_root), workspace_root.as_deref())?;
+
+            output::emit(Event::ConfigValue {
+                key: &get_args.key,
+                setting: config.get(&get_args.key),
+            });
+        }
+        ConfigAction::Set(set_args) => {
+            let source = if set_args.global {
+                Source::Global(
+                    global_config_path().ok_or_else(|| GrumpyError::InvalidConfig {
+                        location: "global config".to_string(),
+                        message: "Unable to locate the config directory".to_string(),
+                    })?,
+                )
+            } else {
+                Source::Project(project_root.join(PROJECT_CONFIG_FILE))
+            };
+
+            write_setting(&source, &set_args.key, &set_args.value)?;
+
+            output::emit(Event::ConfigUpdated {
+                key: &set_args.key,
+                location: &source.to_string(),
+            });
+        }
+    }
+
+    Ok(())
+}
+
+/// Updates a single key in a config file, creating the file if needed and keeping any existing
+/// formatting and comments.
+fn write_setting(source: &Source, key: &str, raw: &str) -> Result<(), GrumpyError> {
+    let path = match source {
+        Source::Global(path) | Source::Project(path) => path,
+        _ => unreachable!("settings are only written to config files"),
+    };
+    let location = source.to_string();
+
+    let spec = key_spec(key).ok_or_else(|| GrumpyError::UnknownConfigKey {
+        key: key.to_string(),
+    })?;
+
+    let value = parse_value(spec, raw)
+        .and_then(|value| validate(key, spec, &value).map(|()| value))
+        .map_err(|message| GrumpyError::InvalidConfig {
+            location: location.clone(),
+            message,
+        })?;
+
+    let mut document = if path.exists() {
+        read_document(path, &location)?
+    } else {
+        DocumentMut::new()
+    };
+
+    let (section, name) = key.split_at(key.find('.').unwrap());
+    let name = &name[1..];
+
+    // A missing section gets a table of its own, rather than an inline one.
+    let table = document
+        .entry(section)
+        .or_insert(toml_edit::table())
+        .as_table_like_mut()
+        .ok_or_else(|| GrumpyError::InvalidConfig {
+            location: location.clone(),
+            message: format!("[{}] is not a table", section),
+        })?;
+
+    let item = match value {
+        Value::Str(value) => toml_edit::value(value),
+        Value::Bool(value) => toml_edit::value(value),
+        Value::List(values) => {
+            let mut array = Array::new();
+
+            for value in values {
+                array.push(value);
+            }
+
+            toml_edit::value(array)
+        }
+    };
+
+    table.insert(name, item);
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(io_error("create", parent))?;
+    }
+
+    fs::write(path, document.to_string()).map_err(io_error("write", path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spec(key: &str) -> &'static KeySpec {
+        key_spec(key).unwrap()
+    }
+
+    #[test]
+    fn knows_fixed_and_profile_keys() {
+        assert!(key_spec("new.script-name").is_some());
+        assert_eq!(spec("profiles.web").key, "profiles.<name>");
+        assert!(key_spec("profiles.").is_none());
+        assert!(key_spec("new.unknown").is_none());
+    }
+
+    #[test]
+    fn lists_are_comma_separated() {
+        assert_eq!(
+            parse_value(spec("dependencies.bin-profiles"), " cli, service ,,"),
+            Ok(Value::List(vec!["cli".to_string(), "service".to_string()]))
+        );
+        assert_eq!(
+            parse_value(spec("templates.default"), "a,b"),
+            Ok(Value::Str("a,b".to_string()))
+        );
+    }
+
+    #[test]
+    fn flags_are_true_or_false() {
+        assert_eq!(
+            parse_value(spec("new.project-files"), "true"),
+            Ok(Value::Bool(true))
+        );
+        assert!(parse_value(spec("new.project-files"), "yes").is_err());
+    }
+
+    #[test]
+    fn checks_values() {
+        let script = Value::Str("tool.rs".to_string());
+        let bad_script = Value::Str("bin/tool.rs".to_string());
+        let bad_template = Value::Str("..\\harness".to_string());
+        let bad_profile = Value::List(vec!["log".to_string()]);
+
+        assert!(validate("new.script-name", spec("new.script-name"), &script).is_ok());
+        assert!(validate("new.script-name", spec("new.script-name"), &bad_script).is_err());
+        assert!(validate(
+            "templates.default",
+            spec("templates.default"),
+            &bad_template
+        )
+        .is_err());
+        assert!(validate("profiles.web", spec("profiles.web"), &bad_profile).is_err());
+    }
+
+    fn config_with_profiles(profiles: &[(&str, &[&str])]) -> Config {
+        let mut settings = BTreeMap::new();
+
+        for (name, dependencies) in profiles {
+            settings.insert(
+                format!("{}{}", PROFILE_PREFIX, name),
+                Setting {
+                    value: Value::List(dependencies.iter().map(|d| d.to_string()).collect()),
+                    source: Source::Default,
+                },
+            );
+        }
+
+        Config { settings }
+    }
+
+    #[test]
+    fn later_profiles_win() {
+        let config = config_with_profiles(&[
+            ("base", &["log@0.4", "dev:log@0.3", "anyhow@1.0"]),
+            ("newer", &["log@0.5"]),
+        ]);
+
+        let dependencies = config
+            .profile_dependencies(&["base".to_string(), "newer".to_string()])
+            .unwrap();
+        let specs: Vec<String> = dependencies.iter().map(|d| d.to_string()).collect();
+
+        assert_eq!(specs, ["dev:log@0.3", "anyhow@1.0", "log@0.5"]);
+    }
+
+    #[test]
+    fn unknown_profiles_are_refused() {
+        let config = config_with_profiles(&[("base", &["log@0.4"])]);
+
+        assert!(config
+            .profile_dependencies(&["missing".to_string()])
+            .is_err());
+    }
+
+    #[test]
+    fn shows_values_as_toml() {
+        assert_eq!(Value::Str("a".to_string()).to_string(), "\"a\"");
+        assert_eq!(
+            Value::List(vec!["a".to_string(), "b".to_string()]).to_string(),
+            "[\"a\", \"b\"]"
+        );
+    }
+}
@@ -0,0 +1,158 @@
+use crate::error::{io_error, GrumpyError};
+use crate::manifest::Manifest;
+use crate::output;
+use crate::project::Project;
+use crate::targets::{Placement, Targets};
+use crate::transaction::Transaction;
+use argh::FromArgs;
+use std::path::Path;
+use std::str::FromStr;
+
+/// What a project can be converted to.
+#[derive(PartialEq, Debug)]
+pub enum ConvertTarget {
+    /// A library, with the existing binary alongside it under `src/bin`.
+    Lib,
+}
+
+impl FromStr for ConvertTarget {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "lib" => Ok(ConvertTarget::Lib),
+            _ => Err(format!("unknown conversion {:?}, expected lib", s)),
+        }
+    }
+}
+
+#[derive(FromArgs, PartialEq, Debug)]
+/// change the layout of an existing project
+#[argh(subcommand, name = "convert")]
+pub struct ConvertSubCommand {
+    /// name of project
+    #[argh(option, short = 'p')]
+    project_name: Option<String>,
+
+    /// path to the project's Cargo.toml
+    #[argh(option)]
+    manifest_path: Option<String>,
+
+    /// what to convert to: lib, for a library plus binaries under src/bin
+    #[argh(option)]
+    to: ConvertTarget,
+}
+
+/// Turns a binary-only package into a library with binaries, so that more binaries can be added
+/// next to the existing one. `src/main.rs` moves to `src/bin/<name>.rs` and an empty `src/lib.rs`
+/// is created, with Cargo.toml updated wherever it names either file.
+///
+/// Returns whether anything was changed, as a package that already has a library is left alone.
+pub fn convert_to_lib(
+    transaction: &mut Transaction,
+    project_root: &Path,
+) -> Result<bool, GrumpyError> {
+    let targets = Targets::load(transaction, project_root)?;
+
+    if targets.has_lib() {
+        return Ok(false);
+    }
+
+    let source_root = project_root.join("src");
+    let mut manifest = Manifest::load(transaction, project_root)?;
+
+    if let Placement::Main(main_path) = targets.placement(project_root)? {
+        if transaction.exists(&main_path) {
+            let name = match targets.bins().first() {
+                Some(bin) => bin.name.clone(),
+                None => manifest.package_name().unwrap_or_default().to_string(),
+            };
+
+            let source = transaction
+                .read(&main_path)
+                .map_err(io_error("read", &main_path))?;
+
+            // Modules in other files are found relative to main.rs, so they'd be lost by the move.
+            if declares_file_modules(&String::from_utf8_lossy(&source)) {
+                return Err(GrumpyError::UnsupportedLayout {
+                    path: main_path,
+                    message: "it declares modules in other files, which would need to move too"
+                        .to_string(),
+                });
+            }
+
+            let bin_path = source_root.join("bin").join(format!("{}.rs", name));
+
+            if transaction.exists(&bin_path) {
+                return Err(GrumpyError::AlreadyExists { path: bin_path });
+            }
+
+            transaction
+                .write(&bin_path, &source)
+                .map_err(io_error("write", &bin_path))?;
+            transaction
+                .remove_file(&main_path)
+                .map_err(io_error("remove", &main_path))?;
+
+            let relative_path = format!("src/bin/{}.rs", name);
+
+            if !manifest.set_bin_path(&name, &relative_path) && !manifest.autobins() {
+                manifest.add_bin_target(&name, &relative_path)?;
+            }
+
+            output::note(&format!(
+                "Moved {} to {}",
+                main_path.display(),
+                bin_path.display()
+            ));
+        }
+    }
+
+    let lib_path = source_root.join("lib.rs");
+
+    transaction
+        .write(&lib_path, b"")
+        .map_err(io_error("write", &lib_path))?;
+
+    if !manifest.autolib() {
+        manifest.set_lib_path("src/lib.rs")?;
+    }
+
+    manifest.save(transaction)?;
+
+    Ok(true)
+}
+
+/// Whether a source file has any `mod name;` declarations, which load the module from a file.
+pub fn declares_file_modules(source: &str) -> bool {
+    source.lines().any(|line| {
+        let line = line.trim();
+        let line = line
+            .strip_prefix("pub(crate) ")
+            .or_else(|| line.strip_prefix("pub "))
+            .unwrap_or(line);
+
+        line.starts_with("mod ") && line.ends_with(';')
+    })
+}
+
+pub fn process_convert(convert_args: &ConvertSubCommand, dry_run: bool) -> Result<(), GrumpyError> {
+    let project = Project::locate(
+        convert_args.project_name.as_ref(),
+        convert_args.manifest_path.as_ref(),
+    )?;
+
+    let mut transaction = Transaction::new(dry_run);
+
+    let converted = match convert_args.to {
+        ConvertTarget::Lib => convert_to_lib(&mut transaction, &project.root)?,
+    };
+
+    if !converted {
+        output::note("The project already has a library, so there's nothing to convert");
+    }
+
+    transaction.commit();
+
+    Ok(())
+}
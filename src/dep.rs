@@ -0,0 +1,307 @@
+use crate::error::GrumpyError;
+use crate::manifest::{Dependency, DependencyChange, DependencyKind, Manifest};
+use crate::output::{self, Event};
+use crate::project::Project;
+use crate::transaction::Transaction;
+use argh::FromArgs;
+use std::path::Path;
+
+#[derive(FromArgs, PartialEq, Debug)]
+/// add, update or remove dependencies in Cargo.toml
+#[argh(subcommand, name = "dep")]
+pub struct DepSubCommand {
+    /// name of project
+    #[argh(option, short = 'p')]
+    project_name: Option<String>,
+
+    /// path to the project's Cargo.toml
+    #[argh(option)]
+    manifest_path: Option<String>,
+
+    #[argh(subcommand)]
+    action: DepAction,
+}
+
+#[derive(FromArgs, PartialEq, Debug)]
+#[argh(subcommand)]
+enum DepAction {
+    Add(DepAddAction),
+    Remove(DepRemoveAction),
+}
+
+#[derive(FromArgs, PartialEq, Debug)]
+/// add dependencies, or update the version of ones already present
+#[argh(subcommand, name = "add")]
+struct DepAddAction {
+    /// dependencies to add, as name@version
+    #[argh(positional)]
+    dependencies: Vec<String>,
+
+    /// add as dev-dependencies
+    #[argh(switch)]
+    dev: bool,
+
+    /// add as build-dependencies
+    #[argh(switch)]
+    build: bool,
+}
+
+#[derive(FromArgs, PartialEq, Debug)]
+/// remove dependencies
+#[argh(subcommand, name = "remove")]
+struct DepRemoveAction {
+    /// names of the dependencies to remove
+    #[argh(positional)]
+    names: Vec<String>,
+
+    /// remove from dev-dependencies
+    #[argh(switch)]
+    dev: bool,
+
+    /// remove from build-dependencies
+    #[argh(switch)]
+    build: bool,
+}
+
+fn requested_kind(dev: bool, build: bool) -> Result<DependencyKind, GrumpyError> {
+    match (dev, build) {
+        (true, true) => Err(GrumpyError::ConflictingOptions("--dev", "--build")),
+        (true, false) => Ok(DependencyKind::Dev),
+        (false, true) => Ok(DependencyKind::Build),
+        (false, false) => Ok(DependencyKind::Normal),
+    }
+}
+
+/// Adds dependencies to the project's Cargo.toml, editing it in place.
+///
+/// In a workspace member, each dependency's version goes into the workspace's
+/// `[workspace.dependencies]`, and the member inherits it with `name = { workspace = true }`, so
+/// every member uses the same version.
+///
+/// A dependency that can't be added doesn't stop the others, but every failure is listed at the
+/// end and makes the whole call fail.
+pub fn add_dependencies(
+    transaction: &mut Transaction,
+    project_root: &Path,
+    workspace_root: Option<&Path>,
+    dependencies: &[Dependency],
+) -> Result<(), GrumpyError> {
+    let mut manifest = Manifest::load(transaction, project_root)?;
+
+    // A package that is its own workspace root has nothing to inherit from.
+    let mut workspace = match workspace_root.filter(|root| *root != project_root) {
+        Some(workspace_root) => Some(Manifest::load(transaction, workspace_root)?),
+        None => None,
+    };
+
+    let mut failures = vec![];
+
+    for dependency in dependencies {
+        let change = match &mut workspace {
+            Some(workspace) => add_inherited(&mut manifest, workspace, dependency),
+            None => manifest
+                .add_dependency(dependency)
+                .map(|change| (change, dependency.kind.table_name())),
+        };
+
+        match change {
+            Ok((DependencyChange::Added, table)) => {
+                output::emit(Event::DependencyAdded { dependency, table })
+            }
+            Ok((DependencyChange::Updated(previous), table)) => {
+                output::emit(Event::DependencyUpdated {
+                    dependency,
+                    previous: &previous,
+                    table,
+                })
+            }
+            Ok((DependencyChange::Unchanged, _)) => {}
+            Ok((DependencyChange::Skipped, table_name)) => output::emit(Event::DependencySkipped {
+                name: &dependency.name,
+                kind: dependency.kind,
+                message: format!(
+                    "Not changing {} in [{}], it isn't a plain version dependency",
+                    dependency.name, table_name
+                ),
+            }),
+            Err(error) => failures.push((dependency, error)),
+        }
+    }
+
+    if let Some(workspace) = &workspace {
+        workspace.save(transaction)?;
+    }
+
+    manifest.save(transaction)?;
+
+    if !failures.is_empty() {
+        for (dependency, error) in &failures {
+            output::emit(Event::DependencyFailed { dependency, error });
+        }
+
+        return Err(GrumpyError::DependenciesFailed {
+            failed: failures.len(),
+            total: dependencies.len(),
+        });
+    }
+
+    Ok(())
+}
+
+/// Adds a dependency to the workspace and has the member inherit it. A change to the version is
+/// reported ahead of the member switching over to inheriting it, along with the table the change
+/// was made in.
+fn add_inherited(
+    manifest: &mut Manifest,
+    workspace: &mut Manifest,
+    dependency: &Dependency,
+) -> Result<(DependencyChange, &'static str), GrumpyError> {
+    // Kept so the member can be put back if the workspace can't take the dependency, rather than
+    // left inheriting one the workspace doesn't declare.
+    let original = manifest.clone();
+    let inherited = manifest.inherit_dependency(dependency)?;
+
+    // A member's own path or git dependency stays as it is, and keeps the workspace out of it.
+    if inherited == DependencyChange::Skipped {
+        return Ok((inherited, dependency.kind.table_name()));
+    }
+
+    match workspace.add_workspace_dependency(dependency) {
+        Ok(DependencyChange::Unchanged) => Ok((inherited, dependency.kind.table_name())),
+        Ok(shared) => Ok((shared, "workspace.dependencies")),
+        Err(error) => {
+            *manifest = original;
+            Err(error)
+        }
+    }
+}
+
+/// Removes dependencies from one of the dependency tables, noting any that weren't there.
+pub fn remove_dependencies(
+    transaction: &mut Transaction,
+    project_root: &Path,
+    kind: DependencyKind,
+    names: &[String],
+) -> Result<(), GrumpyError> {
+    let mut manifest = Manifest::load(transaction, project_root)?;
+
+    for name in names {
+        if manifest.remove_dependency(kind, name) {
+            output::emit(Event::DependencyRemoved { name, kind });
+        } else {
+            output::emit(Event::DependencySkipped {
+                name,
+                kind,
+                message: format!("{} is not in [{}]", name, kind.table_name()),
+            });
+        }
+    }
+
+    manifest.save(transaction)
+}
+
+pub fn process_dep(dep_args: &DepSubCommand, dry_run: bool) -> Result<(), GrumpyError> {
+    let project = Project::locate(
+        dep_args.project_name.as_ref(),
+        dep_args.manifest_path.as_ref(),
+    )?;
+    let project_root = project.root;
+    let workspace_root = project.workspace_root;
+
+    let mut transaction = Transaction::new(dry_run);
+
+    let result = match &dep_args.action {
+        DepAction::Add(add_args) => {
+            let kind = requested_kind(add_args.dev, add_args.build)?;
+
+            let mut dependencies = vec![];
+
+            for spec in &add_args.dependencies {
+                let mut dependency = Dependency::parse(spec)?;
+
+                if dependency.kind == DependencyKind::Normal {
+                    dependency.kind = kind;
+                }
+
+                dependencies.push(dependency);
+            }
+
+            add_dependencies(
+                &mut transaction,
+                &project_root,
+                workspace_root.as_deref(),
+                &dependencies,
+            )
+        }
+        DepAction::Remove(remove_args) => {
+            let kind = requested_kind(remove_args.dev, remove_args.build)?;
+
+            remove_dependencies(&mut transaction, &project_root, kind, &remove_args.names)
+        }
+    };
+
+    // Anything that went wrong only ever leaves the manifest unsaved, so keep what was done.
+    transaction.commit();
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dependency(spec: &str) -> Dependency {
+        Dependency::parse(spec).unwrap()
+    }
+
+    #[test]
+    fn new_version_is_added_to_the_workspace() {
+        let mut manifest = Manifest::parse("[dependencies]\n");
+        let mut workspace = Manifest::parse("[workspace]\n\n[workspace.dependencies]\n");
+
+        let (change, table) =
+            add_inherited(&mut manifest, &mut workspace, &dependency("log@0.4")).unwrap();
+
+        assert_eq!(change, DependencyChange::Added);
+        assert_eq!(table, "workspace.dependencies");
+        assert_eq!(
+            manifest.to_string(),
+            "[dependencies]\nlog = { workspace = true }\n"
+        );
+        assert!(workspace.to_string().contains("log = \"0.4\""));
+    }
+
+    #[test]
+    fn version_already_in_the_workspace_is_reported_for_the_member() {
+        let mut manifest = Manifest::parse("[dependencies]\nlog = \"0.3\"\n");
+        let mut workspace = Manifest::parse("[workspace.dependencies]\nlog = \"0.4\"\n");
+
+        let (change, table) =
+            add_inherited(&mut manifest, &mut workspace, &dependency("log@0.4")).unwrap();
+
+        assert_eq!(change, DependencyChange::Updated("0.3".to_string()));
+        assert_eq!(table, "dependencies");
+    }
+
+    #[test]
+    fn path_dependency_keeps_the_workspace_out_of_it() {
+        let mut manifest = Manifest::parse("[dependencies]\nlog = { path = \"../log\" }\n");
+        let mut workspace = Manifest::parse("[workspace.dependencies]\n");
+
+        let (change, _) =
+            add_inherited(&mut manifest, &mut workspace, &dependency("log@0.4")).unwrap();
+
+        assert_eq!(change, DependencyChange::Skipped);
+        assert_eq!(workspace.to_string(), "[workspace.dependencies]\n");
+    }
+
+    #[test]
+    fn member_is_left_alone_when_the_workspace_fails() {
+        let contents = "[dependencies]\nlog = \"0.3\"\n";
+        let mut manifest = Manifest::parse(contents);
+        let mut workspace = Manifest::parse("workspace = 1\n");
+
+        assert!(add_inherited(&mut manifest, &mut workspace, &dependency("log@0.4")).is_err());
+        assert_eq!(manifest.to_string(), contents);
+    }
+}
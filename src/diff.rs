@@ -0,0 +1,241 @@
+/// Lines of unchanged context shown around each change, as `diff -u` does.
+const CONTEXT: usize = 3;
+
+/// The label for the side of a diff where the file doesn't exist.
+pub const NO_FILE: &str = "/dev/null";
+
+#[derive(Clone, Copy)]
+enum Line<'a> {
+    Same(&'a str),
+    Removed(&'a str),
+    Added(&'a str),
+}
+
+/// Produces a unified diff between two versions of a file, or an empty string if they match.
+///
+/// `old_label` and `new_label` go in the `---` and `+++` headers, so use `/dev/null` for a file
+/// that's being created or deleted. Such a diff always has its headers, even for an empty file.
+pub fn unified_diff(old: &str, new: &str, old_label: &str, new_label: &str) -> String {
+    let old_lines: Vec<&str> = old.lines().collect();
+    let new_lines: Vec<&str> = new.lines().collect();
+    let lines = diff_lines(&old_lines, &new_lines);
+
+    let changes: Vec<usize> = lines
+        .iter()
+        .enumerate()
+        .filter(|(_, line)| !matches!(line, Line::Same(_)))
+        .map(|(index, _)| index)
+        .collect();
+
+    let created_or_deleted = old_label == NO_FILE || new_label == NO_FILE;
+
+    if changes.is_empty() && !created_or_deleted {
+        return String::new();
+    }
+
+    let mut output = format!("--- {}\n+++ {}\n", old_label, new_label);
+
+    // Changes close enough together that their context would overlap share a hunk.
+    let mut hunk_start = 0;
+
+    while hunk_start < changes.len() {
+        let mut hunk_end = hunk_start;
+
+        while hunk_end + 1 < changes.len()
+            && changes[hunk_end + 1] - changes[hunk_end] <= 2 * CONTEXT + 1
+        {
+            hunk_end += 1;
+        }
+
+        let first = changes[hunk_start].saturating_sub(CONTEXT);
+        let last = (changes[hunk_end] + CONTEXT).min(lines.len() - 1);
+
+        output.push_str(&format_hunk(&lines, first, last));
+
+        hunk_start = hunk_end + 1;
+    }
+
+    output
+}
+
+fn format_hunk(lines: &[Line], first: usize, last: usize) -> String {
+    let old_before = lines[..first]
+        .iter()
+        .filter(|line| !matches!(line, Line::Added(_)))
+        .count();
+    let new_before = lines[..first]
+        .iter()
+        .filter(|line| !matches!(line, Line::Removed(_)))
+        .count();
+
+    let hunk = &lines[first..=last];
+    let old_count = hunk
+        .iter()
+        .filter(|line| !matches!(line, Line::Added(_)))
+        .count();
+    let new_count = hunk
+        .iter()
+        .filter(|line| !matches!(line, Line::Removed(_)))
+        .count();
+
+    // An empty range is given as the line before it, so a new file starts at -0,0.
+    let old_start = if old_count == 0 {
+        old_before
+    } else {
+        old_before + 1
+    };
+    let new_start = if new_count == 0 {
+        new_before
+    } else {
+        new_before + 1
+    };
+
+    let mut output = format!(
+        "@@ -{},{} +{},{} @@\n",
+        old_start, old_count, new_start, new_count
+    );
+
+    for line in hunk {
+        let (prefix, text) = match line {
+            Line::Same(text) => (' ', text),
+            Line::Removed(text) => ('-', text),
+            Line::Added(text) => ('+', text),
+        };
+
+        output.push(prefix);
+        output.push_str(text);
+        output.push('\n');
+    }
+
+    output
+}
+
+/// Works out which lines were kept, removed and added, using the longest common subsequence of
+/// the two files. Matching lines at the start and end are stripped first, which keeps the table
+/// small for the kind of edits cargo-grumpy makes.
+fn diff_lines<'a>(old: &[&'a str], new: &[&'a str]) -> Vec<Line<'a>> {
+    let prefix = old
+        .iter()
+        .zip(new.iter())
+        .take_while(|(old, new)| old == new)
+        .count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(old, new)| old == new)
+        .count();
+
+    let old_middle = &old[prefix..old.len() - suffix];
+    let new_middle = &new[prefix..new.len() - suffix];
+
+    // common[i][j] is the length of the longest common subsequence of old_middle[i..] and
+    // new_middle[j..].
+    let width = new_middle.len() + 1;
+    let mut common = vec![0usize; (old_middle.len() + 1) * width];
+
+    for i in (0..old_middle.len()).rev() {
+        for j in (0..new_middle.len()).rev() {
+            common[i * width + j] = if old_middle[i] == new_middle[j] {
+                common[(i + 1) * width + j + 1] + 1
+            } else {
+                common[(i + 1) * width + j].max(common[i * width + j + 1])
+            };
+        }
+    }
+
+    let mut lines: Vec<Line> = old[..prefix].iter().map(|line| Line::Same(line)).collect();
+
+    let (mut i, mut j) = (0, 0);
+
+    while i < old_middle.len() && j < new_middle.len() {
+        if old_middle[i] == new_middle[j] {
+            lines.push(Line::Same(old_middle[i]));
+            i += 1;
+            j += 1;
+        } else if common[(i + 1) * width + j] >= common[i * width + j + 1] {
+            lines.push(Line::Removed(old_middle[i]));
+            i += 1;
+        } else {
+            lines.push(Line::Added(new_middle[j]));
+            j += 1;
+        }
+    }
+
+    lines.extend(old_middle[i..].iter().map(|line| Line::Removed(line)));
+    lines.extend(new_middle[j..].iter().map(|line| Line::Added(line)));
+    lines.extend(
+        old[old.len() - suffix..]
+            .iter()
+            .map(|line| Line::Same(line)),
+    );
+
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matching_files_have_no_diff() {
+        assert_eq!(unified_diff("a\nb\n", "a\nb\n", "a/f", "b/f"), "");
+    }
+
+    #[test]
+    fn changed_line_is_shown_with_context() {
+        let old = "1\n2\n3\n4\n5\n6\n7\n8\n";
+        let new = "1\n2\n3\n4\nfive\n6\n7\n8\n";
+
+        assert_eq!(
+            unified_diff(old, new, "a/f", "b/f"),
+            "--- a/f\n+++ b/f\n@@ -2,7 +2,7 @@\n 2\n 3\n 4\n-5\n+five\n 6\n 7\n 8\n"
+        );
+    }
+
+    #[test]
+    fn distant_changes_get_separate_hunks() {
+        let old: String = (1..=20).map(|n| format!("{}\n", n)).collect();
+        let new = old
+            .replace("2\n3\n", "two\n3\n")
+            .replace("19\n", "nineteen\n");
+
+        let diff = unified_diff(&old, &new, "a/f", "b/f");
+
+        assert_eq!(diff.matches("@@ -").count(), 2);
+        assert!(diff.contains("@@ -1,5 +1,5 @@\n 1\n-2\n+two\n"));
+        assert!(diff.contains("@@ -16,5 +16,5 @@\n 16\n 17\n 18\n-19\n+nineteen\n 20\n"));
+    }
+
+    #[test]
+    fn new_file_starts_at_zero() {
+        assert_eq!(
+            unified_diff("", "fn main() {}\n", NO_FILE, "b/src/main.rs"),
+            "--- /dev/null\n+++ b/src/main.rs\n@@ -0,0 +1,1 @@\n+fn main() {}\n"
+        );
+    }
+
+    #[test]
+    fn empty_new_file_still_has_headers() {
+        assert_eq!(
+            unified_diff("", "", NO_FILE, "b/src/lib.rs"),
+            "--- /dev/null\n+++ b/src/lib.rs\n"
+        );
+    }
+
+    #[test]
+    fn empty_deleted_file_still_has_headers() {
+        assert_eq!(
+            unified_diff("", "", "a/src/lib.rs", NO_FILE),
+            "--- a/src/lib.rs\n+++ /dev/null\n"
+        );
+    }
+
+    #[test]
+    fn inserted_line_keeps_surrounding_lines() {
+        assert_eq!(
+            unified_diff("a\nc\n", "a\nb\nc\n", "a/f", "b/f"),
+            "--- a/f\n+++ b/f\n@@ -1,2 +1,3 @@\n a\n+b\n c\n"
+        );
+    }
+}
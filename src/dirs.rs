@@ -0,0 +1,16 @@
+use std::env;
+use std::path::PathBuf;
+
+/// The per-user cargo-grumpy configuration directory.
+///
+/// This follows the XDG convention of `$XDG_CONFIG_HOME/cargo-grumpy`, falling back to
+/// `~/.config/cargo-grumpy`, or `%APPDATA%\cargo-grumpy` on Windows.
+pub fn user_config_dir() -> Option<PathBuf> {
+    let base = env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .or_else(|| env::var_os("APPDATA").map(PathBuf::from))?;
+
+    Some(base.join("cargo-grumpy"))
+}
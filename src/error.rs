@@ -0,0 +1,383 @@
+use crate::output::{self, Event};
+use argh::FromArgs;
+use std::io;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+/// Everything that can stop cargo-grumpy from doing what it was asked. Each variant has its own
+/// exit code, listed in `EXPLANATIONS`.
+#[derive(Debug, Error)]
+pub enum GrumpyError {
+    #[error("Must only specify one of {0} or {1}")]
+    ConflictingOptions(&'static str, &'static str),
+
+    #[error("`{command}` ended unexpectedly: {status}")]
+    CargoTerminated { command: String, status: String },
+
+    #[error("Not overwriting {path:?} in existing project")]
+    WouldOverwriteMain { path: PathBuf },
+
+    #[error("Not creating {path:?}, it already exists")]
+    AlreadyExists { path: PathBuf },
+
+    #[error("Specified a project name but appear to be inside a project already")]
+    ProjectNameInsideProject,
+
+    #[error("No project name specified, and not inside a project")]
+    NoProjectName,
+
+    #[error("No template named {name:?} found")]
+    TemplateNotFound { name: String },
+
+    #[error("{message} in {location}")]
+    InvalidConfig { location: String, message: String },
+
+    #[error("Unable to parse {path:?}: {message}")]
+    InvalidManifest { path: PathBuf, message: String },
+
+    #[error("Unable to {action} {path:?}: {source}")]
+    Io {
+        action: &'static str,
+        path: PathBuf,
+        source: io::Error,
+    },
+
+    #[error("Unknown variable {variable:?} in template {template:?}")]
+    UnknownTemplateVariable { template: String, variable: String },
+
+    #[error("Unknown dependency profile {name:?}")]
+    UnknownProfile { name: String },
+
+    #[error(
+        "Invalid dependency {spec:?}, expected name@version with an optional dev: or build: prefix"
+    )]
+    InvalidDependency { spec: String },
+
+    #[error("Failed to add {failed} of {total} dependencies")]
+    DependenciesFailed { failed: usize, total: usize },
+
+    #[error("`{command}` failed with exit code {code}")]
+    CargoFailed { command: String, code: u32 },
+
+    #[error("Unable to run cargo: {message}")]
+    CargoUnavailable { message: String },
+
+    #[error("Unknown config key {key:?}")]
+    UnknownConfigKey { key: String },
+
+    #[error("No Cargo.toml found at {path:?}")]
+    ManifestNotFound { path: PathBuf },
+
+    #[error("{path:?} is a virtual workspace manifest, with no package of its own")]
+    VirtualManifest { path: PathBuf },
+
+    #[error("Unable to change the package at {path:?}: {message}")]
+    UnsupportedLayout { path: PathBuf, message: String },
+
+    #[error("No binary named {name:?} in the project")]
+    BinNotFound { name: String },
+
+    #[error("No workspace found at or above {path:?}")]
+    NotInWorkspace { path: PathBuf },
+
+    #[error("No workspace member named {name:?}")]
+    MemberNotFound { name: String },
+
+    #[error("Not linking, it would make a dependency cycle: {cycle}")]
+    DependencyCycle { cycle: String },
+
+    #[error("Invalid project spec {path:?}: {message}")]
+    InvalidSpec { path: PathBuf, message: String },
+
+    #[error("Invalid {kind} name {name:?}: {reason}{}", suggestion_hint(.suggestion))]
+    InvalidName {
+        kind: &'static str,
+        name: String,
+        reason: String,
+        suggestion: Option<String>,
+    },
+
+    #[error("{code} is not an exit code used by cargo-grumpy")]
+    UnknownExitCode { code: i32 },
+}
+
+impl GrumpyError {
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            GrumpyError::ConflictingOptions(..) => 1,
+            GrumpyError::CargoTerminated { .. } => 100,
+            GrumpyError::WouldOverwriteMain { .. } => 101,
+            GrumpyError::AlreadyExists { .. } => 102,
+            GrumpyError::ProjectNameInsideProject => 103,
+            GrumpyError::NoProjectName => 104,
+            GrumpyError::TemplateNotFound { .. } => 105,
+            GrumpyError::InvalidConfig { .. } => 106,
+            GrumpyError::InvalidManifest { .. } => 107,
+            GrumpyError::Io { .. } => 108,
+            GrumpyError::UnknownTemplateVariable { .. } => 109,
+            GrumpyError::UnknownProfile { .. } => 110,
+            GrumpyError::InvalidDependency { .. } => 111,
+            GrumpyError::DependenciesFailed { .. } => 112,
+            GrumpyError::CargoFailed { .. } => 113,
+            GrumpyError::CargoUnavailable { .. } => 114,
+            GrumpyError::UnknownConfigKey { .. } => 115,
+            GrumpyError::ManifestNotFound { .. } => 116,
+            GrumpyError::VirtualManifest { .. } => 117,
+            GrumpyError::UnsupportedLayout { .. } => 118,
+            GrumpyError::InvalidName { .. } => 119,
+            GrumpyError::BinNotFound { .. } => 120,
+            GrumpyError::NotInWorkspace { .. } => 121,
+            GrumpyError::MemberNotFound { .. } => 122,
+            GrumpyError::DependencyCycle { .. } => 123,
+            GrumpyError::InvalidSpec { .. } => 124,
+            GrumpyError::UnknownExitCode { .. } => 125,
+        }
+    }
+}
+
+fn suggestion_hint(suggestion: &Option<String>) -> String {
+    match suggestion {
+        Some(suggestion) => format!(", try {:?} instead", suggestion),
+        None => String::new(),
+    }
+}
+
+/// Builds a `map_err` adapter for filesystem failures, recording what was being done and to what.
+pub fn io_error(action: &'static str, path: &Path) -> impl FnOnce(io::Error) -> GrumpyError {
+    let path = path.to_path_buf();
+
+    move |source| GrumpyError::Io {
+        action,
+        path,
+        source,
+    }
+}
+
+pub struct Explanation {
+    pub code: i32,
+    pub summary: &'static str,
+    pub help: &'static str,
+}
+
+/// What each exit code means and how to get past it. Codes are never reused for something else,
+/// so scripts can rely on them.
+pub const EXPLANATIONS: &[Explanation] = &[
+    Explanation {
+        code: 1,
+        summary: "Conflicting options were given",
+        help: "Two options that can't be used together were both given, such as --bin-only and \
+               --lib-only. Pick one of them.",
+    },
+    Explanation {
+        code: 100,
+        summary: "A cargo command ended unexpectedly",
+        help: "cargo was killed by a signal or otherwise stopped without an exit code. Run the \
+               command shown on its own to see what went wrong.",
+    },
+    Explanation {
+        code: 101,
+        summary: "Refusing to overwrite main.rs",
+        help: "The project only has a binary, so a new script would replace src/main.rs. Run \
+               `cargo grumpy convert --to lib`, or pass --convert to add, to move it to src/bin \
+               next to a new src/lib.rs, so new scripts can go alongside it.",
+    },
+    Explanation {
+        code: 102,
+        summary: "The file or directory to create already exists",
+        help: "cargo-grumpy never overwrites an existing script or project. Pick a different \
+               name, or move the existing one out of the way. `init` refuses a directory that \
+               already has a Cargo.toml, as `add` is the way to add to an existing project.",
+    },
+    Explanation {
+        code: 103,
+        summary: "A project name was given from inside a project",
+        help: "The current directory is inside a project, and --project-name doesn't name a \
+               project below it. Drop the option to use the current project, or run the command \
+               from the directory above.",
+    },
+    Explanation {
+        code: 104,
+        summary: "No project to work on",
+        help: "There's no Cargo.toml in the current directory or any directory above it. Run \
+               the command from inside the project, or name it with --project-name or \
+               --manifest-path.",
+    },
+    Explanation {
+        code: 105,
+        summary: "Template not found",
+        help: "No template of that name exists in the project, team or user template \
+               directories, or built into cargo-grumpy. Check the name given with --template or \
+               templates.default, and that the template file is called <name>.rs.tmpl.",
+    },
+    Explanation {
+        code: 106,
+        summary: "Invalid configuration",
+        help: "A config file or CARGO_GRUMPY_* environment variable has a value of the wrong type \
+               or an unexpected key. The message says where; `cargo grumpy config show` lists \
+               every setting and where it came from.",
+    },
+    Explanation {
+        code: 107,
+        summary: "Cargo.toml couldn't be understood",
+        help: "The project's Cargo.toml isn't valid TOML, or a dependency table isn't a table. \
+               Fix the file by hand, checking it with `cargo metadata`.",
+    },
+    Explanation {
+        code: 108,
+        summary: "A file couldn't be read or written",
+        help: "The filesystem refused a change, usually because of permissions or a full disk. \
+               Commands that change the project put back anything they had already changed. \
+               `config set` writes the config file directly, so check it if the write failed \
+               partway.",
+    },
+    Explanation {
+        code: 109,
+        summary: "Unknown template variable",
+        help: "A template refers to a {{ variable }} that cargo-grumpy doesn't provide. See the \
+               README for the available variables.",
+    },
+    Explanation {
+        code: 110,
+        summary: "Unknown dependency profile",
+        help: "A profile named with --deps or in dependencies.bin-profiles or \
+               dependencies.lib-profiles isn't defined. Define it under [profiles] in a config \
+               file, or use one of the built-in cli, service or lib-minimal profiles.",
+    },
+    Explanation {
+        code: 111,
+        summary: "Invalid dependency",
+        help: "Dependencies are written as name@version, optionally prefixed with dev: or build:, \
+               such as anyhow@1.0 or dev:pretty_assertions@1.4.",
+    },
+    Explanation {
+        code: 112,
+        summary: "Some dependencies couldn't be added",
+        help: "The failed dependencies are listed above the error. The others were added, unless \
+               you chose to roll back.",
+    },
+    Explanation {
+        code: 113,
+        summary: "A cargo command failed",
+        help: "cargo reported an error, shown above. Run the command on its own to investigate.",
+    },
+    Explanation {
+        code: 114,
+        summary: "cargo couldn't be run",
+        help: "cargo-grumpy runs the cargo named by the CARGO environment variable, or cargo from \
+               the PATH. Make sure it's installed, and run cargo-grumpy as `cargo grumpy`.",
+    },
+    Explanation {
+        code: 115,
+        summary: "Unknown config key",
+        help: "Run `cargo grumpy config show` to list every key cargo-grumpy understands.",
+    },
+    Explanation {
+        code: 116,
+        summary: "Cargo.toml not found",
+        help: "--project-name names a directory below the current one, which must hold a \
+               Cargo.toml. --manifest-path must be the path of a Cargo.toml file itself.",
+    },
+    Explanation {
+        code: 117,
+        summary: "No package to work on in a virtual workspace",
+        help: "The Cargo.toml found only defines a workspace, without a [package] of its own. Run \
+               the command from inside one of the workspace's members, or point at a member with \
+               --project-name or --manifest-path.",
+    },
+    Explanation {
+        code: 118,
+        summary: "The package's layout isn't supported",
+        help: "cargo-grumpy reads the package's targets with `cargo metadata`, and won't add a \
+               binary where it couldn't be built or used, such as alongside a proc-macro or \
+               cdylib-only library. Nor will it move or remove a binary when that would leave \
+               the package broken, such as one whose modules live in other files. The message \
+               says what was found.",
+    },
+    Explanation {
+        code: 119,
+        summary: "Invalid project or script name",
+        help:
+            "Names follow cargo's rules: ASCII letters, numbers, - and _ only, not starting with \
+               a digit, and not a Rust keyword, a crate that ships with Rust (such as test), one \
+               of cargo's build directories (such as deps) or a reserved Windows file name. \
+               Script names can be given with or without .rs. The message suggests a name that \
+               would work.",
+    },
+    Explanation {
+        code: 120,
+        summary: "Binary not found",
+        help: "The project has no binary target of that name. Binaries are named after their \
+               file in src/bin, or their directory for src/bin/<name>/main.rs, unless a [[bin]] \
+               table in Cargo.toml names them otherwise.",
+    },
+    Explanation {
+        code: 121,
+        summary: "No workspace found",
+        help: "`member add` and `link` work on a Cargo workspace, found from the nearest \
+               Cargo.toml with a [workspace] table at or above the current directory. Create one \
+               with `new --workspace`, or point at its Cargo.toml with --manifest-path.",
+    },
+    Explanation {
+        code: 122,
+        summary: "Workspace member not found",
+        help: "Members are named by their package name, which is usually the name of their \
+               directory. Only packages listed in the workspace's `members`, directly or through \
+               a glob such as crates/*, are found.",
+    },
+    Explanation {
+        code: 123,
+        summary: "Linking would make a dependency cycle",
+        help: "cargo can't build crates that depend on each other, so `link` refuses to add a \
+               dependency from one member to another that already depends on it, directly or \
+               through other members. The message shows the cycle. Moving the shared code into \
+               a crate both can depend on breaks it.",
+    },
+    Explanation {
+        code: 124,
+        summary: "Invalid project spec",
+        help: "`apply` reads grumpy.toml from the package root, or the file given with --spec. \
+               It takes `lib = true`, a [package] table of Cargo.toml fields, [dependencies] \
+               with a list of profiles, and [[bin]] and [[file]] tables. The message says what \
+               couldn't be understood.",
+    },
+    Explanation {
+        code: 125,
+        summary: "Unknown exit code",
+        help: "`explain` only knows the codes cargo-grumpy itself exits with. Run `cargo grumpy \
+               explain` without a code to list them all.",
+    },
+];
+
+#[derive(FromArgs, PartialEq, Debug)]
+/// describe an exit code and how to fix the problem behind it
+#[argh(subcommand, name = "explain")]
+pub struct ExplainSubCommand {
+    /// exit code to explain, or every code if none is given
+    #[argh(positional)]
+    code: Option<i32>,
+}
+
+pub fn process_explain(explain_args: &ExplainSubCommand) -> Result<(), GrumpyError> {
+    match explain_args.code {
+        Some(code) => match EXPLANATIONS
+            .iter()
+            .find(|explanation| explanation.code == code)
+        {
+            Some(explanation) => output::emit(Event::ExitCode {
+                explanation,
+                detailed: true,
+            }),
+            None => return Err(GrumpyError::UnknownExitCode { code }),
+        },
+        None => {
+            for explanation in EXPLANATIONS {
+                output::emit(Event::ExitCode {
+                    explanation,
+                    detailed: false,
+                });
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,327 @@
+use crate::config::Config;
+use crate::current_dir;
+use crate::error::{io_error, GrumpyError};
+use crate::list::{self, HarnessStatus};
+use crate::manifest::{DependencyKind, Manifest};
+use crate::output;
+use crate::project::Project;
+use crate::rename;
+use crate::spec::{BinSpec, FileSpec, Spec, SPEC_FILE};
+use crate::targets::{manifest_relative, Layout, Target, Targets};
+use crate::template::{TemplateEngine, GENERATED_MARKER};
+use crate::transaction::Transaction;
+use argh::FromArgs;
+use std::path::Path;
+
+/// Fields under `[package]` that describe the project rather than a release of it, so belong in a
+/// spec. The name, version and edition are left to cargo.
+const EXPORTED_FIELDS: &[&str] = &[
+    "description",
+    "license",
+    "license-file",
+    "repository",
+    "homepage",
+    "documentation",
+    "readme",
+    "keywords",
+    "categories",
+];
+
+#[derive(FromArgs, PartialEq, Debug)]
+/// write a grumpy.toml describing the project as it is
+#[argh(subcommand, name = "export")]
+pub struct ExportSubCommand {
+    /// name of project
+    #[argh(option, short = 'p')]
+    project_name: Option<String>,
+
+    /// path to the project's Cargo.toml
+    #[argh(option)]
+    manifest_path: Option<String>,
+
+    /// where to write the spec, defaults to grumpy.toml at the root of the package
+    #[argh(option)]
+    spec: Option<String>,
+
+    /// replace an existing spec
+    #[argh(switch)]
+    force: bool,
+}
+
+/// How a binary is laid out, going by where its source is.
+fn layout(bin: &Target) -> Layout {
+    let dir = bin.src_path.parent();
+
+    let in_own_dir = bin
+        .src_path
+        .file_name()
+        .is_some_and(|file| file == "main.rs")
+        && dir
+            .and_then(Path::file_name)
+            .is_some_and(|dir| dir == bin.name.as_str())
+        && dir
+            .and_then(Path::parent)
+            .is_some_and(|parent| parent.ends_with("src/bin"));
+
+    if in_own_dir {
+        Layout::Dir
+    } else {
+        Layout::File
+    }
+}
+
+/// Describes each binary, along with the template it was rendered from if its header says so.
+fn export_bins(
+    transaction: &Transaction,
+    engine: &TemplateEngine,
+    targets: &Targets,
+) -> Result<Vec<BinSpec>, GrumpyError> {
+    let mut bins = vec![];
+
+    for bin in targets.bins() {
+        // A directory template names each file as <template>/<file>.
+        let template = match list::harness_status(transaction, engine, &bin.src_path)? {
+            HarnessStatus::Generated { header, .. } => header
+                .template
+                .split('/')
+                .next()
+                .map(|template| template.to_string()),
+            HarnessStatus::NotGenerated => None,
+        };
+
+        bins.push(BinSpec {
+            name: bin.name.clone(),
+            template,
+            layout: layout(bin),
+        });
+    }
+
+    Ok(bins)
+}
+
+/// A dependency as profiles list it: the crate name and where it goes.
+type Wanted = (String, DependencyKind);
+
+/// Picks out the profiles whose crates are all `present`, dropping any whose crates all come with
+/// a bigger one. Returns them along with the present dependencies no matching profile accounts for.
+fn choose_profiles<'a>(
+    present: &'a [Wanted],
+    profiles: Vec<(&str, Vec<Wanted>)>,
+) -> (Vec<String>, Vec<&'a str>) {
+    let matched: Vec<(&str, Vec<Wanted>)> = profiles
+        .into_iter()
+        .filter(|(_, crates)| {
+            !crates.is_empty() && crates.iter().all(|wanted| present.contains(wanted))
+        })
+        .collect();
+
+    fn covers(bigger: &[Wanted], smaller: &[Wanted]) -> bool {
+        smaller.iter().all(|wanted| bigger.contains(wanted))
+    }
+
+    let mut chosen = vec![];
+
+    for (index, (profile, crates)) in matched.iter().enumerate() {
+        // Of two profiles with the same crates, only the first is kept.
+        let redundant = matched.iter().enumerate().any(|(other_index, (_, other))| {
+            other_index != index
+                && covers(other, crates)
+                && (other.len() > crates.len() || other_index < index)
+        });
+
+        if !redundant {
+            chosen.push(profile.to_string());
+        }
+    }
+
+    let unaccounted = present
+        .iter()
+        .filter(|dependency| {
+            !matched
+                .iter()
+                .any(|(_, crates)| crates.contains(*dependency))
+        })
+        .map(|(name, _)| name.as_str())
+        .collect();
+
+    (chosen, unaccounted)
+}
+
+/// The profiles whose crates the project already depends on. A profile whose crates all come
+/// with a bigger one is left out, as is any dependency no profile accounts for, which is noted.
+fn export_profiles(config: &Config, manifest: &Manifest) -> Result<Vec<String>, GrumpyError> {
+    let mut present = vec![];
+
+    for kind in &[
+        DependencyKind::Normal,
+        DependencyKind::Dev,
+        DependencyKind::Build,
+    ] {
+        for name in manifest.dependency_names(*kind) {
+            present.push((name, *kind));
+        }
+    }
+
+    let mut profiles = vec![];
+
+    for profile in config.profile_names() {
+        let crates = config
+            .profile_dependencies(&[profile.to_string()])?
+            .into_iter()
+            .map(|dependency| (dependency.name, dependency.kind))
+            .collect();
+
+        profiles.push((profile, crates));
+    }
+
+    let (profiles, unaccounted) = choose_profiles(&present, profiles);
+
+    if !unaccounted.is_empty() {
+        output::note(&format!(
+            "Not in any profile, so not part of the spec: {}",
+            unaccounted.join(", ")
+        ));
+    }
+
+    Ok(profiles)
+}
+
+/// The README and CI files cargo-grumpy generated, which go into the spec as they are now.
+fn export_files(
+    transaction: &Transaction,
+    project_root: &Path,
+) -> Result<Vec<FileSpec>, GrumpyError> {
+    let mut files = vec![];
+
+    for path in rename::generated_files(project_root) {
+        if !transaction.exists(&path) {
+            continue;
+        }
+
+        let contents = transaction
+            .read_to_string(&path)
+            .map_err(io_error("read", &path))?;
+
+        if contents.contains(GENERATED_MARKER) {
+            files.push(FileSpec {
+                path: manifest_relative(project_root, &path),
+                contents,
+            });
+        }
+    }
+
+    Ok(files)
+}
+
+pub fn process_export(export_args: &ExportSubCommand, dry_run: bool) -> Result<(), GrumpyError> {
+    let project = Project::locate(
+        export_args.project_name.as_ref(),
+        export_args.manifest_path.as_ref(),
+    )?;
+    let project_root = project.root;
+    let config = Config::load(Some(&project_root), project.workspace_root.as_deref())?;
+
+    let spec_path = match &export_args.spec {
+        Some(spec_path) => current_dir()?.join(spec_path),
+        None => project_root.join(SPEC_FILE),
+    };
+
+    if spec_path.exists() && !export_args.force {
+        return Err(GrumpyError::AlreadyExists { path: spec_path });
+    }
+
+    let mut transaction = Transaction::new(dry_run);
+
+    let manifest = Manifest::load(&transaction, &project_root)?;
+    let targets = Targets::load(&transaction, &project_root)?;
+
+    let team_dir = config.get_str("templates.team-dir").map(Path::new);
+    let engine = TemplateEngine::new(&project_root, team_dir);
+
+    let package = EXPORTED_FIELDS
+        .iter()
+        .filter_map(|key| {
+            let mut value = manifest.package_value(key)?.clone();
+            value.decor_mut().clear();
+
+            Some((key.to_string(), value))
+        })
+        .collect();
+
+    let spec = Spec {
+        package,
+        lib: targets.has_lib(),
+        bins: export_bins(&transaction, &engine, &targets)?,
+        profiles: export_profiles(&config, &manifest)?,
+        files: export_files(&transaction, &project_root)?,
+    };
+
+    transaction
+        .write(&spec_path, spec.to_toml().as_bytes())
+        .map_err(io_error("write", &spec_path))?;
+
+    transaction.commit();
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn normal(names: &[&str]) -> Vec<Wanted> {
+        names
+            .iter()
+            .map(|name| (name.to_string(), DependencyKind::Normal))
+            .collect()
+    }
+
+    #[test]
+    fn profiles_inside_bigger_ones_are_dropped() {
+        let present = normal(&["argh", "anyhow", "serde"]);
+        let (chosen, unaccounted) = choose_profiles(
+            &present,
+            vec![
+                ("errors", normal(&["anyhow"])),
+                ("cli", normal(&["argh", "anyhow"])),
+            ],
+        );
+
+        assert_eq!(chosen, ["cli"]);
+        assert_eq!(unaccounted, ["serde"]);
+    }
+
+    #[test]
+    fn first_of_identical_profiles_is_kept() {
+        let present = normal(&["argh"]);
+        let (chosen, unaccounted) = choose_profiles(
+            &present,
+            vec![("cli", normal(&["argh"])), ("args", normal(&["argh"]))],
+        );
+
+        assert_eq!(chosen, ["cli"]);
+        assert!(unaccounted.is_empty());
+    }
+
+    #[test]
+    fn profiles_need_every_crate_of_the_right_kind() {
+        let present = vec![
+            ("argh".to_string(), DependencyKind::Normal),
+            ("insta".to_string(), DependencyKind::Normal),
+        ];
+        let (chosen, unaccounted) = choose_profiles(
+            &present,
+            vec![
+                ("cli", normal(&["argh", "anyhow"])),
+                (
+                    "snapshots",
+                    vec![("insta".to_string(), DependencyKind::Dev)],
+                ),
+                ("empty", vec![]),
+            ],
+        );
+
+        assert!(chosen.is_empty());
+        assert_eq!(unaccounted, ["argh", "insta"]);
+    }
+}
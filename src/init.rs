@@ -0,0 +1,303 @@
+use crate::config::Config;
+use crate::dep;
+use crate::error::{io_error, GrumpyError};
+use crate::names;
+use crate::output;
+use crate::project;
+use crate::targets::{Layout, Placement, Targets};
+use crate::transaction::Transaction;
+use crate::{
+    create_binary_script, current_dir, offer_roll_back, plan_cargo_new, requested_profiles,
+    CargoCommand,
+};
+use argh::FromArgs;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Everything `cargo init` might create or change inside the package, relative to its root.
+const CARGO_INIT_PATHS: &[&str] = &[
+    "Cargo.toml",
+    ".gitignore",
+    ".hgignore",
+    ".git",
+    ".hg",
+    "src",
+    "src/main.rs",
+    "src/lib.rs",
+];
+
+#[derive(FromArgs, PartialEq, Debug)]
+/// create a project in an existing directory, keeping any code already there
+#[argh(subcommand, name = "init")]
+pub struct InitSubCommand {
+    /// directory to create the project in, defaults to the current directory
+    #[argh(option)]
+    path: Option<String>,
+
+    /// package name, defaults to the directory name
+    #[argh(option)]
+    name: Option<String>,
+
+    /// create a binary-only project, when there are no sources yet
+    #[argh(switch, short = 'b')]
+    bin_only: bool,
+
+    /// create a library-only project, without the harness
+    #[argh(switch, short = 'l')]
+    lib_only: bool,
+
+    #[argh(option, short = 's')]
+    /// what to call the executable script, defaults to new.script-name from config
+    script_name: Option<String>,
+
+    #[argh(option, short = 't')]
+    /// template to generate the executable script from, defaults to templates.default from config
+    template: Option<String>,
+
+    #[argh(option, short = 'd')]
+    /// dependency profiles to add, defaults to dependencies.bin-profiles and/or
+    /// dependencies.lib-profiles from config
+    deps: Vec<String>,
+
+    /// replace an existing script with the harness
+    #[argh(switch)]
+    force: bool,
+
+    /// rust edition to use, passed to cargo
+    #[argh(option)]
+    edition: Option<String>,
+
+    /// version control system to set up (git, hg, pijul, fossil or none), passed to cargo
+    #[argh(option)]
+    vcs: Option<String>,
+
+    /// registry to publish to, passed to cargo
+    #[argh(option)]
+    registry: Option<String>,
+
+    /// run cargo without accessing the network
+    #[argh(switch)]
+    offline: bool,
+
+    /// further arguments for cargo init, given after --
+    #[argh(positional)]
+    cargo_args: Vec<String>,
+}
+
+/// What a path held before `cargo init` ran.
+enum Before {
+    Missing,
+    Dir,
+    File(Vec<u8>),
+}
+
+/// Records what `cargo init` might change, including a workspace manifest above the package that
+/// it may add the package to.
+fn snapshot(project_root: &Path) -> Result<Vec<(PathBuf, Before)>, GrumpyError> {
+    let mut paths: Vec<PathBuf> = CARGO_INIT_PATHS
+        .iter()
+        .map(|path| project_root.join(path))
+        .collect();
+
+    if let Some(parent_root) = project_root.parent().and_then(project::find_root) {
+        paths.push(parent_root.join("Cargo.toml"));
+    }
+
+    let mut snapshot = vec![];
+
+    for path in paths {
+        let before = if path.is_dir() {
+            Before::Dir
+        } else if path.is_file() {
+            Before::File(fs::read(&path).map_err(io_error("read", &path))?)
+        } else {
+            Before::Missing
+        };
+
+        snapshot.push((path, before));
+    }
+
+    Ok(snapshot)
+}
+
+/// Adds whatever `cargo init` created or changed to the transaction, so it can be undone.
+fn track_changes(transaction: &mut Transaction, snapshot: Vec<(PathBuf, Before)>) {
+    for (path, before) in snapshot {
+        match before {
+            Before::Missing if path.is_dir() => transaction.track_created_tree(&path),
+            Before::Missing if path.is_file() => transaction.track_written_file(&path, None),
+            Before::File(original)
+                if !fs::read(&path).is_ok_and(|contents| contents == original) =>
+            {
+                transaction.track_written_file(&path, Some(original))
+            }
+            _ => {}
+        }
+    }
+}
+
+/// The file the harness would be written over, if there's one in the way.
+fn existing_script(
+    transaction: &Transaction,
+    targets: &Targets,
+    project_root: &Path,
+    bin_name: &str,
+) -> Result<Option<PathBuf>, GrumpyError> {
+    let path = match targets.placement(project_root)? {
+        Placement::Main(main_path) => main_path,
+        Placement::BinDir { dir, .. } => match targets.bin(bin_name) {
+            Some(bin) => bin.src_path.clone(),
+            None => dir.join(Layout::File.source_path(bin_name)),
+        },
+    };
+
+    Ok(Some(path).filter(|path| transaction.exists(path)))
+}
+
+pub fn process_init(init_args: &InitSubCommand, dry_run: bool) -> Result<(), GrumpyError> {
+    if init_args.bin_only && init_args.lib_only {
+        return Err(GrumpyError::ConflictingOptions("--bin-only", "--lib-only"));
+    }
+
+    let project_root = match &init_args.path {
+        Some(path) => current_dir()?.join(path),
+        None => current_dir()?,
+    };
+
+    let package_name = match &init_args.name {
+        Some(name) => name.clone(),
+        None => project_root
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default(),
+    };
+
+    // Check names before cargo gets to them, as its errors don't say what would work instead.
+    names::validate_project_name(&package_name)?;
+
+    if let Some(script_name) = &init_args.script_name {
+        names::script_name(script_name)?;
+    }
+
+    let manifest_path = project_root.join("Cargo.toml");
+
+    if manifest_path.exists() {
+        return Err(GrumpyError::AlreadyExists {
+            path: manifest_path,
+        });
+    }
+
+    let source_root = project_root.join("src");
+    let has_sources = source_root.join("main.rs").is_file() || source_root.join("lib.rs").is_file();
+
+    let mut cargo_command = CargoCommand::new("init");
+
+    // Existing sources decide what kind of package it is, so only ask for one when there are none.
+    if !has_sources {
+        if init_args.bin_only {
+            cargo_command.add_arg("--bin");
+        } else {
+            cargo_command.add_arg("--lib");
+        }
+    }
+
+    cargo_command
+        .add_option("--name", init_args.name.as_ref())
+        .add_option("--edition", init_args.edition.as_ref())
+        .add_option("--vcs", init_args.vcs.as_ref())
+        .add_option("--registry", init_args.registry.as_ref());
+
+    if init_args.offline {
+        cargo_command.add_arg("--offline");
+    }
+
+    for arg in &init_args.cargo_args {
+        cargo_command.add_arg(arg);
+    }
+
+    cargo_command.add_arg(&project_root.to_string_lossy());
+
+    let mut transaction = Transaction::new(dry_run);
+
+    if dry_run {
+        transaction.plan_command(&cargo_command.description());
+
+        plan_cargo_new(
+            &mut transaction,
+            &project_root,
+            &package_name,
+            init_args.edition.as_deref(),
+            !init_args.bin_only,
+        )
+        .map_err(io_error("create", &project_root))?;
+    } else if project_root.exists() {
+        let snapshot = snapshot(&project_root)?;
+
+        cargo_command.run()?;
+
+        // From here on, any failure puts the directory back the way it was.
+        track_changes(&mut transaction, snapshot);
+    } else {
+        cargo_command.run()?;
+
+        transaction.track_created_tree(&project_root);
+    }
+
+    // cargo adds a package made inside a workspace to it, so its dependencies come from there.
+    let workspace_root = project::enclosing_workspace(&project_root)?;
+    let config = Config::load(Some(&project_root), workspace_root.as_deref())?;
+    let targets = Targets::load(&transaction, &project_root)?;
+
+    let mut default_profile_keys = vec![];
+
+    if targets.has_lib() {
+        default_profile_keys.push("dependencies.lib-profiles");
+    }
+
+    if !init_args.lib_only || !targets.bins().is_empty() {
+        default_profile_keys.push("dependencies.bin-profiles");
+    }
+
+    let dependencies = config.profile_dependencies(&requested_profiles(
+        &config,
+        &init_args.deps,
+        &default_profile_keys,
+    ))?;
+
+    if !init_args.lib_only {
+        let script_name = names::script_name(match &init_args.script_name {
+            Some(script_name) => script_name.as_str(),
+            None => config.get_str("new.script-name").unwrap_or("main.rs"),
+        })?;
+
+        match existing_script(&transaction, &targets, &project_root, &script_name)? {
+            Some(path) if !init_args.force => output::note(&format!(
+                "Keeping {}, pass --force to replace it with the harness",
+                path.display()
+            )),
+            _ => create_binary_script(
+                &mut transaction,
+                &config,
+                &project_root,
+                &script_name,
+                init_args.template.as_ref(),
+                Layout::File,
+                init_args.force,
+            )?,
+        }
+    }
+
+    if let Err(error) = dep::add_dependencies(
+        &mut transaction,
+        &project_root,
+        workspace_root.as_deref(),
+        &dependencies,
+    ) {
+        offer_roll_back(transaction);
+        return Err(error);
+    }
+
+    transaction.commit();
+
+    Ok(())
+}
@@ -0,0 +1,384 @@
+use crate::error::{io_error, GrumpyError};
+use crate::manifest::{DependencyChange, DependencyKind, Manifest};
+use crate::output::{self, Event};
+use crate::project;
+use crate::targets::Targets;
+use crate::transaction::Transaction;
+use crate::workspace::{self, Member};
+use argh::FromArgs;
+use std::collections::{HashMap, VecDeque};
+use std::path::{Path, PathBuf};
+
+#[derive(FromArgs, PartialEq, Debug)]
+/// make one workspace member depend on another
+#[argh(subcommand, name = "link")]
+pub struct LinkSubCommand {
+    /// path to the workspace's Cargo.toml
+    #[argh(option)]
+    manifest_path: Option<String>,
+
+    /// the member to add the dependency to
+    #[argh(positional)]
+    from: String,
+
+    /// the member to depend on
+    #[argh(positional)]
+    to: String,
+
+    /// re-export the dependency from the library of the member it's added to, with `pub use`
+    #[argh(switch)]
+    reexport: bool,
+}
+
+fn find_member<'a>(members: &'a [Member], name: &str) -> Result<&'a Member, GrumpyError> {
+    members
+        .iter()
+        .find(|member| member.name == name)
+        .ok_or_else(|| GrumpyError::MemberNotFound {
+            name: name.to_string(),
+        })
+}
+
+/// The members through which `to` already depends on `from`, starting with `to` and ending with
+/// `from`, which a dependency from `from` to `to` would close into a cycle. Dev-dependencies are
+/// left out, as cargo builds those separately.
+fn find_cycle(
+    transaction: &Transaction,
+    members: &[Member],
+    from: &str,
+    to: &str,
+) -> Result<Option<Vec<String>>, GrumpyError> {
+    let mut graph = HashMap::new();
+
+    for member in members {
+        let manifest = Manifest::load(transaction, &member.root)?;
+
+        let mut dependencies = manifest.dependency_packages(DependencyKind::Normal);
+        dependencies.extend(manifest.dependency_packages(DependencyKind::Build));
+        dependencies.retain(|dependency| members.iter().any(|other| &other.name == dependency));
+
+        graph.insert(member.name.as_str(), dependencies);
+    }
+
+    // Searching breadth first finds the shortest cycle, which is the easiest one to read.
+    let mut reached_from: HashMap<&str, &str> = HashMap::new();
+    let mut queue = VecDeque::from(vec![to]);
+
+    while let Some(name) = queue.pop_front() {
+        if name == from {
+            let mut chain = vec![name.to_string()];
+            let mut current = name;
+
+            while let Some(previous) = reached_from.get(current) {
+                chain.push(previous.to_string());
+                current = previous;
+            }
+
+            chain.reverse();
+
+            return Ok(Some(chain));
+        }
+
+        for dependency in graph.get(name).into_iter().flatten() {
+            if dependency != to && !reached_from.contains_key(dependency.as_str()) {
+                reached_from.insert(dependency, name);
+                queue.push_back(dependency);
+            }
+        }
+    }
+
+    Ok(None)
+}
+
+/// The path from one member's directory to another's, given both relative to the workspace root.
+fn relative_path(from: &str, to: &str) -> String {
+    fn components(path: &str) -> Vec<&str> {
+        path.split('/')
+            .filter(|c| !c.is_empty() && *c != ".")
+            .collect()
+    }
+
+    let from = components(from);
+    let to = components(to);
+    let common = from.iter().zip(&to).take_while(|(a, b)| a == b).count();
+
+    let mut path = vec![".."; from.len() - common];
+    path.extend(&to[common..]);
+
+    if path.is_empty() {
+        ".".to_string()
+    } else {
+        path.join("/")
+    }
+}
+
+/// The path to a member's library, which it must have to be depended on or to re-export from.
+fn library_path(
+    transaction: &Transaction,
+    member: &Member,
+    message: String,
+) -> Result<PathBuf, GrumpyError> {
+    Targets::load(transaction, &member.root)?
+        .lib()
+        .map(|lib| lib.src_path.clone())
+        .ok_or_else(|| GrumpyError::UnsupportedLayout {
+            path: member.root.clone(),
+            message,
+        })
+}
+
+/// Adds `pub use <crate>;` to a library's source, after any inner attributes and doc comments at
+/// the top. Returns `None` when the re-export is already there.
+fn add_reexport(contents: &str, crate_name: &str) -> Option<String> {
+    let line = format!("pub use {};", crate_name);
+
+    if contents.lines().any(|existing| existing.trim() == line) {
+        return None;
+    }
+
+    let header_len: usize = contents
+        .split_inclusive('\n')
+        .take_while(|existing| {
+            let existing = existing.trim_start();
+            existing.starts_with("//!") || existing.starts_with("#![")
+        })
+        .map(str::len)
+        .sum();
+
+    let (header, rest) = contents.split_at(header_len);
+
+    let mut updated = header.to_string();
+
+    if !header.is_empty() {
+        updated.push('\n');
+    }
+
+    updated.push_str(&line);
+    updated.push('\n');
+
+    if !rest.is_empty() && !rest.starts_with('\n') {
+        updated.push('\n');
+    }
+
+    updated.push_str(rest);
+
+    Some(updated)
+}
+
+/// Makes `from` depend on `to`. When the workspace shares dependencies under
+/// `[workspace.dependencies]`, `to` is added there by path and `from` inherits it, and otherwise
+/// `from` gets a path dependency of its own.
+fn link_members(
+    transaction: &mut Transaction,
+    workspace_root: &Path,
+    from: &Member,
+    to: &Member,
+) -> Result<DependencyChange, GrumpyError> {
+    let mut workspace = Manifest::load(transaction, workspace_root)?;
+
+    // A root package shares its Cargo.toml with the workspace, so gets a path of its own.
+    let inherit = from.path != "." && workspace.has_workspace_dependencies();
+
+    if inherit {
+        if workspace.add_workspace_path_dependency(&to.name, &to.path)? == DependencyChange::Skipped
+        {
+            return Err(GrumpyError::UnsupportedLayout {
+                path: workspace_root.to_path_buf(),
+                message: format!(
+                    "[workspace.dependencies] already has a {} that isn't a path dependency",
+                    to.name
+                ),
+            });
+        }
+
+        workspace.save(transaction)?;
+    }
+
+    let mut manifest = Manifest::load(transaction, &from.root)?;
+
+    let change = if inherit {
+        manifest.add_inherited_dependency(DependencyKind::Normal, &to.name)?
+    } else {
+        manifest.add_path_dependency(
+            DependencyKind::Normal,
+            &to.name,
+            &relative_path(&from.path, &to.path),
+        )?
+    };
+
+    if change == DependencyChange::Skipped {
+        return Err(GrumpyError::UnsupportedLayout {
+            path: from.root.clone(),
+            message: format!(
+                "it already depends on a {} from outside the workspace",
+                to.name
+            ),
+        });
+    }
+
+    manifest.save(transaction)?;
+
+    Ok(change)
+}
+
+pub fn process_link(link_args: &LinkSubCommand, dry_run: bool) -> Result<(), GrumpyError> {
+    let workspace_root = project::locate_workspace(link_args.manifest_path.as_ref())?;
+
+    let mut transaction = Transaction::new(dry_run);
+
+    let members = workspace::members(&transaction, &workspace_root)?;
+    let from = find_member(&members, &link_args.from)?;
+    let to = find_member(&members, &link_args.to)?;
+
+    if let Some(chain) = find_cycle(&transaction, &members, &from.name, &to.name)? {
+        return Err(GrumpyError::DependencyCycle {
+            cycle: format!("{} -> {}", from.name, chain.join(" -> ")),
+        });
+    }
+
+    // Check everything that could stop the link before changing anything.
+    library_path(
+        &transaction,
+        to,
+        format!("it has no library for {} to depend on", from.name),
+    )?;
+
+    let reexport_path = if link_args.reexport {
+        Some(library_path(
+            &transaction,
+            from,
+            format!("it has no library to re-export {} from", to.name),
+        )?)
+    } else {
+        None
+    };
+
+    match link_members(&mut transaction, &workspace_root, from, to)? {
+        DependencyChange::Unchanged => {
+            output::note(&format!("{} already depends on {}", from.name, to.name))
+        }
+        _ => output::emit(Event::MemberLinked {
+            from: &from.name,
+            to: &to.name,
+            path: &to.path,
+        }),
+    }
+
+    if let Some(lib_path) = reexport_path {
+        let contents = transaction
+            .read_to_string(&lib_path)
+            .map_err(io_error("read", &lib_path))?;
+
+        match add_reexport(&contents, &to.name.replace('-', "_")) {
+            Some(updated) => transaction
+                .write(&lib_path, updated.as_bytes())
+                .map_err(io_error("write", &lib_path))?,
+            None => output::note(&format!(
+                "{} already re-exports {}",
+                lib_path.display(),
+                to.name
+            )),
+        }
+    }
+
+    transaction.commit();
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn relative_paths_between_members() {
+        assert_eq!(relative_path("crates/cli", "crates/core"), "../core");
+        assert_eq!(relative_path("cli", "crates/core"), "../crates/core");
+        assert_eq!(relative_path(".", "crates/core"), "crates/core");
+        assert_eq!(relative_path("crates/cli", "."), "../..");
+        assert_eq!(relative_path("./core", "core"), ".");
+    }
+
+    #[test]
+    fn reexport_goes_after_the_header() {
+        assert_eq!(
+            add_reexport("//! Docs\n#![deny(missing_docs)]\n\nmod a;\n", "core").unwrap(),
+            "//! Docs\n#![deny(missing_docs)]\n\npub use core;\n\nmod a;\n"
+        );
+        assert_eq!(
+            add_reexport("mod a;\n", "core").unwrap(),
+            "pub use core;\n\nmod a;\n"
+        );
+        assert_eq!(add_reexport("", "core").unwrap(), "pub use core;\n");
+    }
+
+    #[test]
+    fn existing_reexport_is_left() {
+        assert_eq!(add_reexport("mod a;\n    pub use core;\n", "core"), None);
+    }
+
+    /// Members of a workspace planned in a dry run, each depending on those listed with it.
+    fn planned_members(graph: &[(&str, &[&str])]) -> (Transaction, Vec<Member>) {
+        let mut transaction = Transaction::new(true);
+        let workspace_root = PathBuf::from("/nonexistent/cargo-grumpy-workspace");
+        let mut members = vec![];
+
+        for (name, dependencies) in graph {
+            let root = workspace_root.join(name);
+            let mut manifest = format!("[package]\nname = \"{}\"\n\n[dependencies]\n", name);
+
+            for dependency in *dependencies {
+                manifest.push_str(&format!(
+                    "{} = {{ path = \"../{}\" }}\n",
+                    dependency, dependency
+                ));
+            }
+
+            transaction
+                .write(&root.join("Cargo.toml"), manifest.as_bytes())
+                .unwrap();
+
+            members.push(Member {
+                name: name.to_string(),
+                root,
+                path: name.to_string(),
+            });
+        }
+
+        (transaction, members)
+    }
+
+    #[test]
+    fn finds_the_shortest_cycle() {
+        let (transaction, members) =
+            planned_members(&[("app", &[]), ("core", &["util", "app"]), ("util", &["app"])]);
+
+        assert_eq!(
+            find_cycle(&transaction, &members, "app", "core").unwrap(),
+            Some(vec!["core".to_string(), "app".to_string()])
+        );
+        assert_eq!(
+            find_cycle(&transaction, &members, "app", "util").unwrap(),
+            Some(vec!["util".to_string(), "app".to_string()])
+        );
+    }
+
+    #[test]
+    fn no_cycle_without_a_path_back() {
+        let (transaction, members) =
+            planned_members(&[("app", &["core"]), ("core", &["util"]), ("util", &[])]);
+
+        assert_eq!(
+            find_cycle(&transaction, &members, "app", "util").unwrap(),
+            None
+        );
+        assert_eq!(
+            find_cycle(&transaction, &members, "util", "app").unwrap(),
+            Some(vec![
+                "app".to_string(),
+                "core".to_string(),
+                "util".to_string()
+            ])
+        );
+    }
+}
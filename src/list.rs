@@ -0,0 +1,131 @@
+use crate::config::Config;
+use crate::error::{io_error, GrumpyError};
+use crate::output::{self, Event};
+use crate::project::Project;
+use crate::targets::{manifest_relative, Targets};
+use crate::template::{Header, TemplateEngine};
+use crate::transaction::Transaction;
+use argh::FromArgs;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(FromArgs, PartialEq, Debug)]
+/// list the package's targets, and whether each binary still matches its template
+#[argh(subcommand, name = "list")]
+pub struct ListSubCommand {
+    /// name of project
+    #[argh(option, short = 'p')]
+    project_name: Option<String>,
+
+    /// path to the project's Cargo.toml
+    #[argh(option)]
+    manifest_path: Option<String>,
+}
+
+/// Whether a binary was rendered from a template, and how it compares with it now.
+pub enum HarnessStatus {
+    NotGenerated,
+    Generated {
+        /// The header of the binary's main source file.
+        header: Header,
+        /// Whether any of the binary's generated files has been edited since.
+        modified: bool,
+        /// Whether the template has changed since, or `None` if it can't be found any more.
+        template_changed: Option<bool>,
+    },
+}
+
+/// Checks a binary's source against the headers written when it was rendered. A binary laid out
+/// as `<name>/main.rs` has every generated file in its directory checked.
+pub fn harness_status(
+    transaction: &Transaction,
+    engine: &TemplateEngine,
+    src_path: &Path,
+) -> Result<HarnessStatus, GrumpyError> {
+    let contents = transaction
+        .read_to_string(src_path)
+        .map_err(io_error("read", src_path))?;
+
+    let header = match Header::parse(&contents) {
+        Some((header, _)) => header,
+        None => return Ok(HarnessStatus::NotGenerated),
+    };
+
+    let mut modified = false;
+    let mut template_changed = Some(false);
+
+    for path in generated_sources(src_path, &header)? {
+        let contents = transaction
+            .read_to_string(&path)
+            .map_err(io_error("read", &path))?;
+
+        if let Some((file_header, body)) = Header::parse(&contents) {
+            modified |= file_header.is_modified(body);
+
+            template_changed = match (template_changed, engine.version(&file_header.template)) {
+                (Some(changed), Some(version)) => Some(changed || version != file_header.version),
+                _ => None,
+            };
+        }
+    }
+
+    Ok(HarnessStatus::Generated {
+        header,
+        modified,
+        template_changed,
+    })
+}
+
+/// The files rendered along with a binary's main source. Only a directory template renders more
+/// than one.
+fn generated_sources(src_path: &Path, header: &Header) -> Result<Vec<PathBuf>, GrumpyError> {
+    let dir = match src_path.parent() {
+        Some(dir) if header.template.contains('/') => dir,
+        _ => return Ok(vec![src_path.to_path_buf()]),
+    };
+
+    let mut paths = vec![];
+
+    for entry in fs::read_dir(dir).map_err(io_error("read", dir))? {
+        let path = entry.map_err(io_error("read", dir))?.path();
+
+        if path.extension().is_some_and(|extension| extension == "rs") {
+            paths.push(path);
+        }
+    }
+
+    paths.sort();
+
+    Ok(paths)
+}
+
+pub fn process_list(list_args: &ListSubCommand) -> Result<(), GrumpyError> {
+    let project = Project::locate(
+        list_args.project_name.as_ref(),
+        list_args.manifest_path.as_ref(),
+    )?;
+    let config = Config::load(Some(&project.root), project.workspace_root.as_deref())?;
+
+    // Nothing is ever changed, so this is only here to read through.
+    let transaction = Transaction::new(false);
+    let targets = Targets::load(&transaction, &project.root)?;
+
+    let team_dir = config.get_str("templates.team-dir").map(Path::new);
+    let engine = TemplateEngine::new(&project.root, team_dir);
+
+    for target in targets.all() {
+        let harness = if target.kinds.iter().any(|kind| kind == "bin") {
+            Some(harness_status(&transaction, &engine, &target.src_path)?)
+        } else {
+            None
+        };
+
+        output::emit(Event::TargetListed {
+            target,
+            path: &manifest_relative(&project.root, &target.src_path),
+            harness: harness.as_ref(),
+        });
+    }
+
+    Ok(())
+}
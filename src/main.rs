@@ -1,19 +1,64 @@
+mod apply;
+mod config;
+mod convert;
+mod dep;
+mod diff;
+mod dirs;
+mod error;
+mod export;
+mod init;
+mod link;
+mod list;
+mod manifest;
+mod names;
+mod output;
+mod project;
+mod promote;
+mod remove;
+mod rename;
+mod spec;
+mod targets;
+mod template;
+mod transaction;
+mod workspace;
+
+use apply::ApplySubCommand;
 use argh::FromArgs;
+use config::{Config, ConfigSubCommand};
+use convert::ConvertSubCommand;
+use dep::DepSubCommand;
+use error::{io_error, ExplainSubCommand, GrumpyError};
+use export::ExportSubCommand;
+use init::InitSubCommand;
+use link::LinkSubCommand;
+use list::ListSubCommand;
+use manifest::Manifest;
+use output::{Event, MessageFormat};
+use project::Project;
+use promote::PromoteSubCommand;
+use remove::RemoveSubCommand;
+use rename::RenameSubCommand;
 use std::env;
-use std::fs;
-use std::fs::File;
-use std::io::Write;
-use std::path;
-use std::path::PathBuf;
+use std::io::{self, IsTerminal, Write};
+use std::path::{Path, PathBuf};
 use std::process::exit;
-use subprocess::{Exec, ExitStatus};
-use chwd::ChangeWorkingDirectory;
+use subprocess::{Exec, ExitStatus, NullFile, Redirection};
+use targets::{manifest_relative, Layout, Placement, Targets};
+use template::{TemplateContext, TemplateEngine};
+use transaction::Transaction;
+use workspace::MemberSubCommand;
 
 #[derive(FromArgs)]
 /// Harness the power of Grumpy to automate standard project creation and maintenance.
-///
-/// Requires the presence of cargo-edit on the running system.
 struct GrumpyArgs {
+    /// show the changes that would be made, as a diff, without making them
+    #[argh(switch)]
+    dry_run: bool,
+
+    /// how to report what happened: human (the default) or json, for one JSON object per line
+    #[argh(option, default = "MessageFormat::Human")]
+    message_format: MessageFormat,
+
     #[argh(subcommand)]
     sub_command: SubCommandEnum,
 }
@@ -22,14 +67,27 @@ struct GrumpyArgs {
 #[argh(subcommand)]
 enum SubCommandEnum {
     New(NewSubCommand),
+    Init(InitSubCommand),
     Add(AddSubCommand),
+    Config(ConfigSubCommand),
+    Dep(DepSubCommand),
+    Explain(ExplainSubCommand),
+    Convert(ConvertSubCommand),
+    Promote(PromoteSubCommand),
+    Remove(RemoveSubCommand),
+    Rename(RenameSubCommand),
+    List(ListSubCommand),
+    Member(MemberSubCommand),
+    Link(LinkSubCommand),
+    Apply(ApplySubCommand),
+    Export(ExportSubCommand),
 }
 
 #[derive(FromArgs, PartialEq, Debug)]
 /// create a new project
 #[argh(subcommand, name = "new")]
 struct NewSubCommand {
-    /// name of project
+    /// name of project, or the directory to create it in when --name is given
     #[argh(positional)]
     project_name: String,
 
@@ -42,8 +100,46 @@ struct NewSubCommand {
     lib_only: bool,
 
     #[argh(option, short = 's')]
-    /// what to call the executable script, defaults to main
+    /// what to call the executable script, defaults to new.script-name from config
     script_name: Option<String>,
+
+    #[argh(option, short = 't')]
+    /// template to generate the executable script from, defaults to templates.default from config
+    template: Option<String>,
+
+    #[argh(option, short = 'd')]
+    /// dependency profiles to add, defaults to dependencies.bin-profiles and/or
+    /// dependencies.lib-profiles from config
+    deps: Vec<String>,
+
+    /// package name, defaults to the directory name
+    #[argh(option)]
+    name: Option<String>,
+
+    /// rust edition to use, passed to cargo
+    #[argh(option)]
+    edition: Option<String>,
+
+    /// version control system to set up (git, hg, pijul, fossil or none), passed to cargo
+    #[argh(option)]
+    vcs: Option<String>,
+
+    /// registry to publish to, passed to cargo
+    #[argh(option)]
+    registry: Option<String>,
+
+    /// run cargo without accessing the network
+    #[argh(switch)]
+    offline: bool,
+
+    /// create a virtual workspace for members to be added to with `member add`, instead of a
+    /// package
+    #[argh(switch)]
+    workspace: bool,
+
+    /// further arguments for cargo new, given after --
+    #[argh(positional)]
+    cargo_args: Vec<String>,
 }
 
 #[derive(FromArgs, PartialEq, Debug)]
@@ -54,13 +150,39 @@ struct AddSubCommand {
     #[argh(option, short = 'p')]
     project_name: Option<String>,
 
+    /// path to the project's Cargo.toml
+    #[argh(option)]
+    manifest_path: Option<String>,
+
     #[argh(positional)]
     /// what to call the executable script, defaults to main
     script_name: String,
+
+    #[argh(option, short = 't')]
+    /// template to generate the executable script from, defaults to templates.default from config
+    template: Option<String>,
+
+    #[argh(option, short = 'd')]
+    /// dependency profiles to add, defaults to dependencies.bin-profiles from config
+    deps: Vec<String>,
+
+    /// how to lay out the script: file for src/bin/<name>.rs (the default), or dir for
+    /// src/bin/<name>/main.rs with sibling modules from a directory template
+    #[argh(option, default = "Layout::File")]
+    layout: Layout,
+
+    /// convert a binary-only project to a library plus binaries first, so the script can be
+    /// added alongside the existing one
+    #[argh(switch)]
+    convert: bool,
+}
+
+fn current_dir() -> Result<PathBuf, GrumpyError> {
+    env::current_dir().map_err(io_error("read the current directory", Path::new(".")))
 }
 
-fn get_project_path_buf(project_name: &String) -> PathBuf {
-    path::PathBuf::from(env::current_dir().unwrap()).join(project_name)
+fn get_project_path_buf(project_name: &String) -> Result<PathBuf, GrumpyError> {
+    Ok(current_dir()?.join(project_name))
 }
 
 struct CargoCommand {
@@ -82,8 +204,29 @@ impl CargoCommand {
         self
     }
 
-    fn run(&self) -> i32 {
-        let cargo_command = env::var("CARGO").unwrap();
+    /// Adds an option and its value, if there's a value to give.
+    fn add_option(&mut self, option: &str, value: Option<&String>) -> &mut Self {
+        if let Some(value) = value {
+            self.add_arg(option).add_arg(value);
+        }
+
+        self
+    }
+
+    fn description(&self) -> String {
+        let mut description = format!("cargo {}", self.command);
+
+        for arg in &self.args {
+            description.push(' ');
+            description.push_str(arg);
+        }
+
+        description
+    }
+
+    fn exec(&self) -> Exec {
+        // cargo sets CARGO when running us as `cargo grumpy`, but fall back to the PATH otherwise.
+        let cargo_command = env::var("CARGO").unwrap_or_else(|_| "cargo".to_string());
 
         let mut command = Exec::cmd(cargo_command).arg(&self.command);
 
@@ -91,108 +234,484 @@ impl CargoCommand {
             command = command.arg(arg);
         }
 
-        match command.join().unwrap() {
-            ExitStatus::Exited(0) => 0,
-            ExitStatus::Exited(exit_code) => exit_code as i32,
-            other => {
-                println!("Unexpected error code {:?} found", other);
-                100
-            }
+        command
+    }
+
+    /// Reports how the command finished, and turns anything but success into an error.
+    fn check(&self, status: ExitStatus) -> Result<(), GrumpyError> {
+        let mut args = vec![self.command.clone()];
+        args.extend(self.args.iter().cloned());
+
+        output::emit(Event::CargoCommand {
+            command: &self.description(),
+            args: &args,
+            exit_code: match status {
+                ExitStatus::Exited(code) => Some(code),
+                _ => None,
+            },
+        });
+
+        match status {
+            ExitStatus::Exited(0) => Ok(()),
+            ExitStatus::Exited(code) => Err(GrumpyError::CargoFailed {
+                command: self.description(),
+                code,
+            }),
+            other => Err(GrumpyError::CargoTerminated {
+                command: self.description(),
+                status: format!("{:?}", other),
+            }),
         }
     }
-}
 
-fn create_binary_script(project_name: &String, script_name: &String, overwrite: bool) -> i32 {
-    let project_root = get_project_path_buf(project_name);
-    let source_root = project_root.join("src");
+    fn run(&self) -> Result<(), GrumpyError> {
+        let status = self
+            .exec()
+            .join()
+            .map_err(|e| GrumpyError::CargoUnavailable {
+                message: e.to_string(),
+            })?;
 
-    let mut filename: PathBuf;
+        self.check(status)
+    }
 
-    // We have one of two cases here. We're either in a lib project, in which case executable
-    // scripts live under a bin subdirectory. Otherwise, we add directly to the current
-    // project_root.
-    if source_root.join("lib.rs").exists() {
-        // Library, so we want to create any scripts under a bin subdirectory
-        fs::create_dir_all(source_root.join("bin")).unwrap();
+    /// Runs the command with its output captured, returning what it printed to stdout. What it
+    /// printed to stderr is only shown if it fails.
+    fn capture(&self) -> Result<String, GrumpyError> {
+        let capture = self
+            .exec()
+            .stdout(Redirection::Pipe)
+            .stderr(Redirection::Pipe)
+            .capture()
+            .map_err(|e| GrumpyError::CargoUnavailable {
+                message: e.to_string(),
+            })?;
+
+        if !capture.exit_status.success() {
+            eprint!("{}", capture.stderr_str());
+        }
 
-        let new_script_path = source_root.join("bin").join(script_name);
+        self.check(capture.exit_status)?;
 
-        if new_script_path.exists() {
-            println!("Not creating {:?}, file already exists", new_script_path);
-            return 102;
-        } else {
-            filename = new_script_path;
-        };
+        Ok(capture.stdout_str())
+    }
+}
+
+/// Falls back to git's configured user name when the manifest doesn't list any authors.
+fn git_user_name() -> Option<String> {
+    let capture = Exec::cmd("git")
+        .arg("config")
+        .arg("user.name")
+        .stdout(Redirection::Pipe)
+        .stderr(NullFile)
+        .capture()
+        .ok()?;
+
+    if capture.exit_status.success() {
+        Some(capture.stdout_str().trim().to_string())
     } else {
-        // Binary project, add to the root
-        let binary_source_file = source_root.join("main.rs");
-
-        if binary_source_file.exists() {
-            if overwrite {
-                fs::remove_file(&binary_source_file).unwrap();
-            } else {
-                println!(
-                    "Not overwriting {:?} in existing project, exiting",
-                    binary_source_file
-                );
-                return 101;
+        None
+    }
+}
+
+fn render_script(
+    transaction: &Transaction,
+    config: &Config,
+    project_root: &Path,
+    script_name: &str,
+    template_name: &str,
+    layout: Layout,
+) -> Result<Vec<(PathBuf, String)>, GrumpyError> {
+    let manifest = Manifest::load(transaction, project_root)?;
+
+    let project_name = manifest
+        .package_name()
+        .map(|name| name.to_string())
+        .unwrap_or_else(|| {
+            project_root
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default()
+        });
+
+    let author = manifest
+        .authors()
+        .first()
+        .cloned()
+        .or_else(git_user_name)
+        .unwrap_or_default();
+
+    // A workspace member may inherit its edition, in which case the workspace has it.
+    let edition = match manifest.edition() {
+        Some(edition) => edition.to_string(),
+        None => match project::enclosing_workspace(project_root)? {
+            Some(workspace_root) => Manifest::load(transaction, &workspace_root)?
+                .workspace_package_str("edition")
+                .unwrap_or("2015")
+                .to_string(),
+            None => "2015".to_string(),
+        },
+    };
+
+    let mut context = TemplateContext::new();
+
+    context
+        .set("project_name", &project_name)
+        .set("script_name", script_name)
+        .set("author", &author)
+        .set("year", &template::current_year().to_string())
+        .set("edition", &edition);
+
+    let team_dir = config.get_str("templates.team-dir").map(Path::new);
+
+    let engine = TemplateEngine::new(project_root, team_dir);
+
+    match layout {
+        Layout::File => Ok(vec![(
+            layout.source_path(script_name),
+            engine.render(template_name, &context)?,
+        )]),
+        Layout::Dir => Ok(engine
+            .render_dir(template_name, &context)?
+            .into_iter()
+            .map(|(file_name, contents)| (Path::new(script_name).join(file_name), contents))
+            .collect()),
+    }
+}
+
+/// Splits `--deps` values, which may be repeated or comma separated, into profile names. Falls
+/// back to the given config keys when no profiles were asked for.
+fn requested_profiles(config: &Config, deps: &[String], default_keys: &[&str]) -> Vec<String> {
+    let profiles: Vec<String> = deps
+        .iter()
+        .flat_map(|deps| deps.split(','))
+        .map(|profile| profile.trim())
+        .filter(|profile| !profile.is_empty())
+        .map(|profile| profile.to_string())
+        .collect();
+
+    if !profiles.is_empty() {
+        return profiles;
+    }
+
+    let mut profiles: Vec<String> = vec![];
+
+    for key in default_keys {
+        for profile in config.get_list(key) {
+            if !profiles.contains(profile) {
+                profiles.push(profile.clone());
             }
         }
+    }
+
+    profiles
+}
+
+/// Asks a yes or no question on the terminal, defaulting to no. Always answers no when stdin
+/// isn't a terminal, so scripted runs never block.
+fn confirm(question: &str) -> bool {
+    if !io::stdin().is_terminal() {
+        return false;
+    }
+
+    // Keep the prompt off stdout when that's carrying JSON.
+    match output::format() {
+        MessageFormat::Human => {
+            print!("{} [y/N] ", question);
+            io::stdout().flush().ok();
+        }
+        MessageFormat::Json => {
+            eprint!("{} [y/N] ", question);
+            io::stderr().flush().ok();
+        }
+    }
 
-        filename = binary_source_file;
+    let mut answer = String::new();
+
+    if io::stdin().read_line(&mut answer).is_err() {
+        return false;
     }
 
-    filename.set_extension("rs");
+    matches!(answer.trim().to_lowercase().as_str(), "y" | "yes")
+}
+
+/// Offers to undo everything done so far once adding dependencies has failed, keeping it all if
+/// the answer is no.
+fn offer_roll_back(transaction: Transaction) {
+    if transaction.is_dry_run() {
+        // Nothing was changed, but still show what the run got as far as planning.
+        transaction.commit();
+        return;
+    }
 
-    if filename.exists() {
-        println!("Not creating {:?}, already exists", filename);
-        return 102;
-    } else {
-        // Target script doesn't exist, we can create it now.
-        let mut script = File::create(filename).unwrap();
+    if !confirm("Roll back the changes made so far?") {
+        output::note("Leaving changes in place");
+        transaction.commit();
+        return;
+    }
 
-        script
-            .write(
-                b"\
-use anyhow::Error;
+    match transaction.roll_back() {
+        Ok(()) => output::note("Rolled back changes"),
+        Err(e) => output::note(&format!("Unable to fully roll back changes: {}", e)),
+    }
+}
 
-fn run() -> Result<(), Error> {
-    println!(\"Hello, world!\");
+/// Reports a script that won't be written, and why, before giving up on it.
+fn skipped(path: &Path, error: GrumpyError) -> GrumpyError {
+    output::emit(Event::FileSkipped {
+        path,
+        message: &error.to_string(),
+    });
 
-    Ok(())
+    error
 }
 
-fn main() -> Result<(), Error> {
-    run()?;
+/// Renders a script from a template into the package, wherever cargo will find it. With
+/// `overwrite`, files already in the way are replaced rather than refused.
+fn create_binary_script(
+    transaction: &mut Transaction,
+    config: &Config,
+    project_root: &Path,
+    bin_name: &str,
+    template_name: Option<&String>,
+    layout: Layout,
+    overwrite: bool,
+) -> Result<(), GrumpyError> {
+    let template_name = template_name
+        .map(|name| name.as_str())
+        .or_else(|| config.get_str("templates.default"))
+        .unwrap_or(template::DEFAULT_TEMPLATE);
+
+    // Render up front, so a broken template doesn't leave us having already removed main.rs.
+    let mut files = render_script(
+        transaction,
+        config,
+        project_root,
+        bin_name,
+        template_name,
+        layout,
+    )?;
+
+    let targets = Targets::load(transaction, project_root)?;
+
+    match targets.placement(project_root)? {
+        Placement::BinDir { dir, declare } => {
+            let source_path = dir.join(layout.source_path(bin_name));
+            let replacing = overwrite && transaction.exists(&source_path);
+
+            if let Some(existing) = targets.bin(bin_name).filter(|_| !replacing) {
+                return Err(skipped(
+                    &source_path,
+                    GrumpyError::AlreadyExists {
+                        path: existing.src_path.clone(),
+                    },
+                ));
+            }
+
+            // cargo refuses to build a binary found in both layouts, so check for the other one.
+            let other_path = match layout {
+                Layout::File => dir.join(bin_name),
+                Layout::Dir => dir.join(format!("{}.rs", bin_name)),
+            };
+
+            if transaction.exists(&other_path) {
+                return Err(skipped(
+                    &source_path,
+                    GrumpyError::AlreadyExists { path: other_path },
+                ));
+            }
+
+            if declare && !replacing {
+                // cargo won't look in src/bin by itself, so tell it about the new binary.
+                let mut manifest = Manifest::load(transaction, project_root)?;
+                let relative_path = manifest_relative(project_root, &source_path);
+
+                manifest.add_bin_target(bin_name, &relative_path)?;
+                manifest.save(transaction)?;
+            }
+
+            for (path, _) in &mut files {
+                *path = dir.join(&*path);
+            }
+        }
+        Placement::Main(binary_source_file) => {
+            if layout == Layout::Dir {
+                return Err(GrumpyError::UnsupportedLayout {
+                    path: project_root.join("Cargo.toml"),
+                    message: "the directory layout needs binaries under src/bin, so convert the \
+                              project to a library first"
+                        .to_string(),
+                });
+            }
+
+            // The package's only binary is main.rs, which a new script can only replace.
+            if transaction.exists(&binary_source_file) {
+                if overwrite {
+                    transaction
+                        .remove_file(&binary_source_file)
+                        .map_err(io_error("remove", &binary_source_file))?;
+                } else {
+                    return Err(skipped(
+                        &binary_source_file,
+                        GrumpyError::WouldOverwriteMain {
+                            path: binary_source_file.clone(),
+                        },
+                    ));
+                }
+            }
+
+            for (path, _) in &mut files {
+                *path = binary_source_file.clone();
+            }
+        }
+    }
+
+    for (filename, contents) in &files {
+        if transaction.exists(filename) && !overwrite {
+            return Err(skipped(
+                filename,
+                GrumpyError::AlreadyExists {
+                    path: filename.clone(),
+                },
+            ));
+        }
+
+        // Target script doesn't exist, or is ours to replace, so we should be able to write it.
+        transaction
+            .write(filename, contents.as_bytes())
+            .map_err(io_error("write", filename))?;
+    }
+
     Ok(())
-}",
-            )
-            .unwrap();
+}
+
+/// Writes a README and CI workflow for a new project, naming its binary. Either file that's
+/// already there is left alone.
+fn write_project_files(
+    transaction: &mut Transaction,
+    project_root: &Path,
+    project_name: &str,
+    bin_name: &str,
+) -> Result<(), GrumpyError> {
+    let mut context = TemplateContext::new();
+
+    context
+        .set("project_name", project_name)
+        .set("bin_name", bin_name);
+
+    for (path, contents) in template::render_project_files(&context)? {
+        let path = project_root.join(path);
+
+        if !transaction.exists(&path) {
+            transaction
+                .write(&path, contents.as_bytes())
+                .map_err(io_error("write", &path))?;
+        }
     }
 
+    Ok(())
+}
+
+/// Stands in for `cargo new` or `cargo init` during a dry run, planning the skeleton it would
+/// create so that later steps have a manifest and sources to work from.
+fn plan_cargo_new(
+    transaction: &mut Transaction,
+    project_root: &Path,
+    package_name: &str,
+    edition: Option<&str>,
+    lib: bool,
+) -> io::Result<()> {
+    transaction.write(
+        &project_root.join("Cargo.toml"),
+        format!(
+            "[package]\nname = \"{}\"\nversion = \"0.1.0\"\nedition = \"{}\"\n\n[dependencies]\n",
+            package_name,
+            edition.unwrap_or("2021")
+        )
+        .as_bytes(),
+    )?;
+
+    let source_root = project_root.join("src");
+
+    // As with `cargo init`, sources that are already there are used as they are.
+    if transaction.exists(&source_root.join("main.rs"))
+        || transaction.exists(&source_root.join("lib.rs"))
     {
-        let _dir_change = ChangeWorkingDirectory::change(&project_root);
+        Ok(())
+    } else if lib {
+        transaction.write(&source_root.join("lib.rs"), b"")
+    } else {
+        transaction.write(
+            &source_root.join("main.rs"),
+            b"fn main() {\n    println!(\"Hello, world!\");\n}\n",
+        )
+    }
+}
+
+/// Creates a virtual workspace. cargo can't make one by itself, so the manifest is written
+/// directly.
+fn process_new_workspace(new_args: &NewSubCommand, dry_run: bool) -> Result<(), GrumpyError> {
+    // Only the edition carries over to a virtual workspace, the rest is for `member add`.
+    let package_options = [
+        (new_args.bin_only, "--bin-only"),
+        (new_args.lib_only, "--lib-only"),
+        (new_args.script_name.is_some(), "--script-name"),
+        (new_args.template.is_some(), "--template"),
+        (!new_args.deps.is_empty(), "--deps"),
+        (new_args.name.is_some(), "--name"),
+        (new_args.vcs.is_some(), "--vcs"),
+        (new_args.registry.is_some(), "--registry"),
+        (new_args.offline, "--offline"),
+        (!new_args.cargo_args.is_empty(), "--"),
+    ];
+
+    if let Some((_, option)) = package_options.iter().find(|(given, _)| *given) {
+        return Err(GrumpyError::ConflictingOptions("--workspace", option));
+    }
+
+    names::validate_project_name(&new_args.project_name)?;
+
+    let workspace_root = get_project_path_buf(&new_args.project_name)?;
 
-        // Now, we'll ensure that Cargo.toml contains the right crate dependencies.
-        // We'll do this by making life easy on ourselves and using cargo-edit facilities to do
-        // the addition.
-        CargoCommand::new("add").add_arg("fehler@1.0").run();
-        CargoCommand::new("add").add_arg("anyhow@1.0").run();
-        CargoCommand::new("add").add_arg("thiserror@1.0").run();
-        CargoCommand::new("add").add_arg("log@0.4").run();
-        CargoCommand::new("add").add_arg("log4rs@1.1").run();
+    if workspace_root.exists() {
+        return Err(GrumpyError::AlreadyExists {
+            path: workspace_root,
+        });
     }
 
-    0
+    let mut transaction = Transaction::new(dry_run);
+
+    workspace::create_workspace(
+        &mut transaction,
+        &workspace_root,
+        new_args.edition.as_deref(),
+    )?;
+
+    transaction.commit();
+
+    Ok(())
 }
 
-fn process_new(new_args: &NewSubCommand) -> i32 {
+fn process_new(new_args: &NewSubCommand, dry_run: bool) -> Result<(), GrumpyError> {
     let bin_only = new_args.bin_only;
     let lib_only = new_args.lib_only;
 
     if bin_only && lib_only {
-        println!("Must only specify one of binary-only or library-only");
-        return 1;
+        return Err(GrumpyError::ConflictingOptions("--bin-only", "--lib-only"));
+    }
+
+    if new_args.workspace {
+        return process_new_workspace(new_args, dry_run);
+    }
+
+    // With --name the project name is only a directory, so it's the package name that's checked.
+    let package_name = new_args.name.as_ref().unwrap_or(&new_args.project_name);
+
+    // Check names before cargo gets to them, as its errors don't say what would work instead.
+    names::validate_project_name(package_name)?;
+
+    if let Some(script_name) = &new_args.script_name {
+        names::script_name(script_name)?;
     }
 
     let mut cargo_command = CargoCommand::new("new");
@@ -203,57 +722,200 @@ fn process_new(new_args: &NewSubCommand) -> i32 {
         cargo_command.add_arg("--lib");
     }
 
+    cargo_command
+        .add_option("--name", new_args.name.as_ref())
+        .add_option("--edition", new_args.edition.as_ref())
+        .add_option("--vcs", new_args.vcs.as_ref())
+        .add_option("--registry", new_args.registry.as_ref());
+
+    if new_args.offline {
+        cargo_command.add_arg("--offline");
+    }
+
+    for arg in &new_args.cargo_args {
+        cargo_command.add_arg(arg);
+    }
+
     cargo_command.add_arg(new_args.project_name.as_str());
 
-    match cargo_command.run() {
-        0 => {}
-        code => return code,
+    let project_root = get_project_path_buf(&new_args.project_name)?;
+    let mut transaction = Transaction::new(dry_run);
+
+    if dry_run {
+        if project_root.exists() {
+            return Err(GrumpyError::AlreadyExists { path: project_root });
+        }
+
+        transaction.plan_command(&cargo_command.description());
+
+        plan_cargo_new(
+            &mut transaction,
+            &project_root,
+            package_name,
+            new_args.edition.as_deref(),
+            !bin_only,
+        )
+        .map_err(io_error("create", &project_root))?;
+    } else {
+        cargo_command.run()?;
+
+        // From here on, any failure removes the half-built project again.
+        transaction.track_created_tree(&project_root);
     }
 
+    // cargo adds a project made inside a workspace to it, so its dependencies come from there.
+    let workspace_root = project::enclosing_workspace(&project_root)?;
+    let config = Config::load(Some(&project_root), workspace_root.as_deref())?;
+
+    // A default project has both a library and a binary, so it gets both sets of profiles.
+    let default_profile_keys: &[&str] = if bin_only {
+        &["dependencies.bin-profiles"]
+    } else if lib_only {
+        &["dependencies.lib-profiles"]
+    } else {
+        &["dependencies.lib-profiles", "dependencies.bin-profiles"]
+    };
+
+    let dependencies = config.profile_dependencies(&requested_profiles(
+        &config,
+        &new_args.deps,
+        default_profile_keys,
+    ))?;
+
     if !lib_only {
-        return create_binary_script(
-            &new_args.project_name,
-            &new_args
-                .script_name
-                .as_ref()
-                .unwrap_or(&"main.rs".to_string()),
+        let script_name = names::script_name(match &new_args.script_name {
+            Some(script_name) => script_name.as_str(),
+            None => config.get_str("new.script-name").unwrap_or("main.rs"),
+        })?;
+
+        create_binary_script(
+            &mut transaction,
+            &config,
+            &project_root,
+            &script_name,
+            new_args.template.as_ref(),
+            Layout::File,
             true,
-        );
+        )?;
+
+        // A binary-only project's script replaces main.rs, so its binary is named after the
+        // package.
+        let bin_name = if bin_only {
+            package_name.as_str()
+        } else {
+            script_name.as_str()
+        };
+
+        if config.get_bool("new.project-files") {
+            write_project_files(&mut transaction, &project_root, package_name, bin_name)?;
+        }
     }
 
-    0
+    if let Err(error) = dep::add_dependencies(
+        &mut transaction,
+        &project_root,
+        workspace_root.as_deref(),
+        &dependencies,
+    ) {
+        offer_roll_back(transaction);
+        return Err(error);
+    }
+
+    transaction.commit();
+
+    Ok(())
 }
 
-fn process_add(add_args: &AddSubCommand) -> i32 {
-    if env::current_dir().unwrap().join("src").exists() {
-        // We're probably inside an existing project, so we want to create something here
-        // without specifying the project name.
+fn process_add(add_args: &AddSubCommand, dry_run: bool) -> Result<(), GrumpyError> {
+    let project = Project::locate(
+        add_args.project_name.as_ref(),
+        add_args.manifest_path.as_ref(),
+    )?;
+    let project_root = project.root;
+    let workspace_root = project.workspace_root;
+    let script_name = names::script_name(&add_args.script_name)?;
 
-        if add_args.project_name.is_some() {
-            // Oops, project name was specified though, so this is probably an error.
-            println!("Specified a project name but appear to be inside a project already");
-            return 103;
-        }
-    } else if add_args.project_name.is_none() {
-        // Not in a directory with a src folder, so we need a project name, but weren't given one.
-        println!("No project name specified");
-        return 104;
+    let config = Config::load(Some(&project_root), workspace_root.as_deref())?;
+
+    let dependencies = config.profile_dependencies(&requested_profiles(
+        &config,
+        &add_args.deps,
+        &["dependencies.bin-profiles"],
+    ))?;
+
+    let mut transaction = Transaction::new(dry_run);
+
+    if add_args.convert {
+        convert::convert_to_lib(&mut transaction, &project_root)?;
     }
 
     create_binary_script(
-        &add_args.project_name.as_ref().unwrap_or(&".".to_string()),
-        &add_args.script_name,
+        &mut transaction,
+        &config,
+        &project_root,
+        &script_name,
+        add_args.template.as_ref(),
+        add_args.layout,
         false,
-    )
+    )?;
+
+    if let Err(error) = dep::add_dependencies(
+        &mut transaction,
+        &project_root,
+        workspace_root.as_deref(),
+        &dependencies,
+    ) {
+        offer_roll_back(transaction);
+        return Err(error);
+    }
+
+    transaction.commit();
+
+    Ok(())
 }
 
 fn main() {
     let args: GrumpyArgs = argh::cargo_from_env();
 
-    let exit_code = match args.sub_command {
-        SubCommandEnum::New(new_args) => process_new(&new_args),
-        SubCommandEnum::Add(add_args) => process_add(&add_args),
+    output::set_format(args.message_format);
+
+    let result = match args.sub_command {
+        SubCommandEnum::New(new_args) => process_new(&new_args, args.dry_run),
+        SubCommandEnum::Init(init_args) => init::process_init(&init_args, args.dry_run),
+        SubCommandEnum::Add(add_args) => process_add(&add_args, args.dry_run),
+        SubCommandEnum::Config(config_args) => config::process_config(&config_args),
+        SubCommandEnum::Dep(dep_args) => dep::process_dep(&dep_args, args.dry_run),
+        SubCommandEnum::Explain(explain_args) => error::process_explain(&explain_args),
+        SubCommandEnum::Convert(convert_args) => {
+            convert::process_convert(&convert_args, args.dry_run)
+        }
+        SubCommandEnum::Promote(promote_args) => {
+            promote::process_promote(&promote_args, args.dry_run)
+        }
+        SubCommandEnum::Remove(remove_args) => remove::process_remove(&remove_args, args.dry_run),
+        SubCommandEnum::Rename(rename_args) => rename::process_rename(&rename_args, args.dry_run),
+        SubCommandEnum::List(list_args) => list::process_list(&list_args),
+        SubCommandEnum::Member(member_args) => {
+            workspace::process_member(&member_args, args.dry_run)
+        }
+        SubCommandEnum::Link(link_args) => link::process_link(&link_args, args.dry_run),
+        SubCommandEnum::Apply(apply_args) => apply::process_apply(&apply_args, args.dry_run),
+        SubCommandEnum::Export(export_args) => export::process_export(&export_args, args.dry_run),
     };
 
-    exit(exit_code);
+    output::emit(Event::Finished {
+        error: result.as_ref().err(),
+    });
+
+    if let Err(error) = result {
+        let exit_code = error.exit_code();
+
+        eprintln!("error: {}", error);
+        eprintln!(
+            "Run `cargo grumpy explain {}` for more information",
+            exit_code
+        );
+
+        exit(exit_code);
+    }
 }
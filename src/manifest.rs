@@ -0,0 +1,1109 @@
+use crate::error::{io_error, GrumpyError};
+use crate::transaction::Transaction;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use toml_edit::{ArrayOfTables, DocumentMut, Entry, InlineTable, Item, Table, TableLike};
+
+/// Which dependency table a dependency belongs in.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DependencyKind {
+    Normal,
+    Dev,
+    Build,
+}
+
+impl DependencyKind {
+    pub fn table_name(self) -> &'static str {
+        match self {
+            DependencyKind::Normal => "dependencies",
+            DependencyKind::Dev => "dev-dependencies",
+            DependencyKind::Build => "build-dependencies",
+        }
+    }
+
+    /// The kind as `cargo metadata` reports it, which is null for normal dependencies.
+    pub fn metadata_kind(self) -> Option<&'static str> {
+        match self {
+            DependencyKind::Normal => None,
+            DependencyKind::Dev => Some("dev"),
+            DependencyKind::Build => Some("build"),
+        }
+    }
+}
+
+/// A dependency as written in profiles and on the command line: `name@version`, optionally
+/// prefixed with `dev:` or `build:` to put it in the matching table.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Dependency {
+    pub name: String,
+    pub version: String,
+    pub kind: DependencyKind,
+}
+
+impl Dependency {
+    pub fn parse(spec: &str) -> Result<Self, GrumpyError> {
+        let (kind, rest) = if let Some(rest) = spec.strip_prefix("dev:") {
+            (DependencyKind::Dev, rest)
+        } else if let Some(rest) = spec.strip_prefix("build:") {
+            (DependencyKind::Build, rest)
+        } else {
+            (DependencyKind::Normal, spec)
+        };
+
+        let mut parts = rest.splitn(2, '@');
+        let name = parts.next().unwrap_or_default();
+        let version = parts.next().unwrap_or_default();
+
+        let valid_name = !name.is_empty()
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+
+        let valid_version = !version.is_empty()
+            && version
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || "^~=<>*.,+- ".contains(c));
+
+        if valid_name && valid_version {
+            Ok(Dependency {
+                name: name.to_string(),
+                version: version.to_string(),
+                kind,
+            })
+        } else {
+            Err(GrumpyError::InvalidDependency {
+                spec: spec.to_string(),
+            })
+        }
+    }
+}
+
+impl fmt::Display for Dependency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.kind {
+            DependencyKind::Normal => write!(f, "{}@{}", self.name, self.version),
+            DependencyKind::Dev => write!(f, "dev:{}@{}", self.name, self.version),
+            DependencyKind::Build => write!(f, "build:{}@{}", self.name, self.version),
+        }
+    }
+}
+
+/// What happened when a dependency was added to a manifest.
+#[derive(Debug, PartialEq)]
+pub enum DependencyChange {
+    Added,
+    Updated(String),
+    Unchanged,
+    /// The existing entry doesn't use a plain version, such as a path or workspace dependency, so
+    /// it was left alone.
+    Skipped,
+}
+
+/// A `[lib]` or `[[bin]]` table, with whatever it says about the target. Anything left out is
+/// up to cargo.
+pub struct DeclaredTarget {
+    pub name: Option<String>,
+    pub path: Option<String>,
+    /// The `crate-type` list, or just `proc-macro` when `proc-macro = true`.
+    pub crate_types: Vec<String>,
+}
+
+impl DeclaredTarget {
+    fn from_table(table: &dyn TableLike) -> Self {
+        let str_value = |key| {
+            table
+                .get(key)
+                .and_then(|value| value.as_str())
+                .map(|value| value.to_string())
+        };
+
+        let crate_types = if table
+            .get("proc-macro")
+            .and_then(|value| value.as_bool())
+            .unwrap_or(false)
+        {
+            vec!["proc-macro".to_string()]
+        } else {
+            table
+                .get("crate-type")
+                .and_then(|types| types.as_array())
+                .into_iter()
+                .flatten()
+                .filter_map(|kind| kind.as_str())
+                .map(|kind| kind.to_string())
+                .collect()
+        };
+
+        DeclaredTarget {
+            name: str_value("name"),
+            path: str_value("path"),
+            crate_types,
+        }
+    }
+}
+
+/// A parsed Cargo.toml, which keeps the original formatting and comments when written back.
+#[derive(Clone)]
+pub struct Manifest {
+    path: PathBuf,
+    document: DocumentMut,
+}
+
+impl Manifest {
+    /// Loads the Cargo.toml found in `project_root`, as the transaction currently sees it.
+    pub fn load(transaction: &Transaction, project_root: &Path) -> Result<Self, GrumpyError> {
+        let path = project_root.join("Cargo.toml");
+
+        let contents = transaction
+            .read_to_string(&path)
+            .map_err(io_error("read", &path))?;
+
+        let document =
+            contents
+                .parse::<DocumentMut>()
+                .map_err(|e| GrumpyError::InvalidManifest {
+                    path: path.clone(),
+                    message: e.to_string(),
+                })?;
+
+        Ok(Manifest { path, document })
+    }
+
+    /// Parses a manifest held in memory, as if it had been loaded from `Cargo.toml`.
+    #[cfg(test)]
+    pub fn parse(contents: &str) -> Self {
+        Manifest {
+            path: PathBuf::from("Cargo.toml"),
+            document: contents.parse().unwrap(),
+        }
+    }
+
+    pub fn save(&self, transaction: &mut Transaction) -> Result<(), GrumpyError> {
+        transaction
+            .write(&self.path, self.document.to_string().as_bytes())
+            .map_err(io_error("write", &self.path))
+    }
+
+    fn package_str(&self, key: &str) -> Option<&str> {
+        self.document
+            .get("package")
+            .and_then(|package| package.get(key))
+            .and_then(|value| value.as_str())
+    }
+
+    pub fn package_name(&self) -> Option<&str> {
+        self.package_str("name")
+    }
+
+    /// The package edition, defaulting to 2015 as cargo does when none is given. `None` when it's
+    /// inherited from the workspace, which has to be asked instead.
+    pub fn edition(&self) -> Option<&str> {
+        if self.inherits("edition") {
+            None
+        } else {
+            Some(self.package_str("edition").unwrap_or("2015"))
+        }
+    }
+
+    /// Whether a package field is inherited from the workspace, as `key.workspace = true`.
+    pub fn inherits(&self, key: &str) -> bool {
+        self.document
+            .get("package")
+            .and_then(|package| package.get(key))
+            .and_then(|field| field.get("workspace"))
+            .and_then(|workspace| workspace.as_bool())
+            .unwrap_or(false)
+    }
+
+    /// A field shared with members under `[workspace.package]`.
+    pub fn workspace_package_str(&self, key: &str) -> Option<&str> {
+        self.document
+            .get("workspace")
+            .and_then(|workspace| workspace.get("package"))
+            .and_then(|package| package.get(key))
+            .and_then(|value| value.as_str())
+    }
+
+    /// Whether cargo finds binaries in `src/bin` by itself. As well as `package.autobins`, the
+    /// 2015 edition turns this off whenever any `[[bin]]` table is given.
+    pub fn autobins(&self) -> bool {
+        let explicit = self
+            .document
+            .get("package")
+            .and_then(|package| package.get("autobins"))
+            .and_then(|autobins| autobins.as_bool());
+
+        explicit
+            .unwrap_or_else(|| self.edition() != Some("2015") || self.document.get("bin").is_none())
+    }
+
+    /// Whether cargo finds `src/lib.rs` by itself.
+    pub fn autolib(&self) -> bool {
+        self.document
+            .get("package")
+            .and_then(|package| package.get("autolib"))
+            .and_then(|autolib| autolib.as_bool())
+            .unwrap_or(true)
+    }
+
+    /// The `[lib]` table, if there is one.
+    pub fn lib_target(&self) -> Option<DeclaredTarget> {
+        self.document
+            .get("lib")
+            .and_then(|lib| lib.as_table_like())
+            .map(DeclaredTarget::from_table)
+    }
+
+    /// The `[[bin]]` tables, in the order they're given.
+    pub fn bin_targets(&self) -> Vec<DeclaredTarget> {
+        self.document
+            .get("bin")
+            .and_then(|bins| bins.as_array_of_tables())
+            .into_iter()
+            .flat_map(|bins| bins.iter())
+            .map(|bin| DeclaredTarget::from_table(bin))
+            .collect()
+    }
+
+    /// Sets the library's source file in the `[lib]` table, adding the table if needed.
+    pub fn set_lib_path(&mut self, path: &str) -> Result<(), GrumpyError> {
+        let manifest_path = &self.path;
+
+        let lib = self
+            .document
+            .entry("lib")
+            .or_insert(toml_edit::table())
+            .as_table_like_mut()
+            .ok_or_else(|| GrumpyError::InvalidManifest {
+                path: manifest_path.clone(),
+                message: "[lib] is not a table".to_string(),
+            })?;
+
+        lib.insert("path", toml_edit::value(path));
+
+        Ok(())
+    }
+
+    /// Declares a binary with a `[[bin]]` table, for packages where cargo won't find it itself.
+    pub fn add_bin_target(&mut self, name: &str, path: &str) -> Result<(), GrumpyError> {
+        let manifest_path = &self.path;
+
+        let bins = self
+            .document
+            .entry("bin")
+            .or_insert(Item::ArrayOfTables(ArrayOfTables::new()))
+            .as_array_of_tables_mut()
+            .ok_or_else(|| GrumpyError::InvalidManifest {
+                path: manifest_path.clone(),
+                message: "bin is not an array of tables".to_string(),
+            })?;
+
+        let mut bin = Table::new();
+        bin["name"] = toml_edit::value(name);
+        bin["path"] = toml_edit::value(path);
+        bins.push(bin);
+
+        Ok(())
+    }
+
+    /// Points an existing `[[bin]]` table at a new source file, returning whether there was a
+    /// table for that binary.
+    pub fn set_bin_path(&mut self, name: &str, path: &str) -> bool {
+        let bin = self
+            .document
+            .get_mut("bin")
+            .and_then(|bins| bins.as_array_of_tables_mut())
+            .and_then(|bins| {
+                bins.iter_mut()
+                    .find(|bin| bin.get("name").and_then(|name| name.as_str()) == Some(name))
+            });
+
+        match bin {
+            Some(bin) => {
+                bin["path"] = toml_edit::value(path);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Removes a binary's `[[bin]]` table, along with `default-run` if it names the binary, so
+    /// cargo isn't left looking for it. Returns whether there was a table for that binary.
+    pub fn remove_bin_target(&mut self, name: &str) -> bool {
+        if let Some(package) = self
+            .document
+            .get_mut("package")
+            .and_then(|package| package.as_table_like_mut())
+        {
+            if package.get("default-run").and_then(|run| run.as_str()) == Some(name) {
+                package.remove("default-run");
+            }
+        }
+
+        let bins = match self
+            .document
+            .get_mut("bin")
+            .and_then(|bins| bins.as_array_of_tables_mut())
+        {
+            Some(bins) => bins,
+            None => return false,
+        };
+
+        let before = bins.len();
+        bins.retain(|bin| bin.get("name").and_then(|name| name.as_str()) != Some(name));
+        let removed = bins.len() != before;
+
+        if bins.is_empty() {
+            self.document.remove("bin");
+        }
+
+        removed
+    }
+
+    /// Renames a binary's `[[bin]]` table, pointing it at `path` if it gives a path, and updates
+    /// `default-run` if it names the binary. Returns whether there was a table for that binary.
+    pub fn rename_bin_target(&mut self, name: &str, new_name: &str, path: &str) -> bool {
+        if let Some(package) = self
+            .document
+            .get_mut("package")
+            .and_then(|package| package.as_table_like_mut())
+        {
+            if package.get("default-run").and_then(|run| run.as_str()) == Some(name) {
+                package.insert("default-run", toml_edit::value(new_name));
+            }
+        }
+
+        let bin = self
+            .document
+            .get_mut("bin")
+            .and_then(|bins| bins.as_array_of_tables_mut())
+            .and_then(|bins| {
+                bins.iter_mut()
+                    .find(|bin| bin.get("name").and_then(|name| name.as_str()) == Some(name))
+            });
+
+        match bin {
+            Some(bin) => {
+                bin["name"] = toml_edit::value(new_name);
+
+                if bin.contains_key("path") {
+                    bin["path"] = toml_edit::value(path);
+                }
+
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Whether any feature in `[features]` enables the dependency, which would stop cargo
+    /// accepting the manifest without it.
+    pub fn features_use(&self, name: &str) -> bool {
+        let features = match self
+            .document
+            .get("features")
+            .and_then(|features| features.as_table_like())
+        {
+            Some(features) => features,
+            None => return false,
+        };
+
+        features
+            .iter()
+            .filter_map(|(_, enables)| enables.as_array())
+            .flatten()
+            .filter_map(|enables| enables.as_str())
+            .any(|enables| {
+                let enables = enables.strip_prefix("dep:").unwrap_or(enables);
+                let enables = enables.split('/').next().unwrap_or_default();
+
+                enables.trim_end_matches('?') == name
+            })
+    }
+
+    /// Names of the dependencies in one of the dependency tables, as written in Cargo.toml.
+    pub fn dependency_names(&self, kind: DependencyKind) -> Vec<String> {
+        self.document
+            .get(kind.table_name())
+            .and_then(|table| table.as_table_like())
+            .map(|table| table.iter().map(|(name, _)| name.to_string()).collect())
+            .unwrap_or_default()
+    }
+
+    /// The packages the dependencies in one of the dependency tables refer to, which differ from
+    /// their names in Cargo.toml when renamed with `package = "..."`.
+    pub fn dependency_packages(&self, kind: DependencyKind) -> Vec<String> {
+        self.document
+            .get(kind.table_name())
+            .and_then(|table| table.as_table_like())
+            .map(|table| {
+                table
+                    .iter()
+                    .map(|(name, entry)| {
+                        entry
+                            .get("package")
+                            .and_then(Item::as_str)
+                            .unwrap_or(name)
+                            .to_string()
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Paths listed in `workspace.members` or `workspace.exclude`, as written, globs and all.
+    pub fn workspace_paths(&self, key: &str) -> Vec<String> {
+        self.document
+            .get("workspace")
+            .and_then(|workspace| workspace.get(key))
+            .and_then(|paths| paths.as_array())
+            .map(|paths| {
+                paths
+                    .iter()
+                    .filter_map(|path| path.as_str())
+                    .map(|path| path.to_string())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Whether the workspace shares dependencies with its members under
+    /// `[workspace.dependencies]`.
+    pub fn has_workspace_dependencies(&self) -> bool {
+        self.document
+            .get("workspace")
+            .and_then(|workspace| workspace.get("dependencies"))
+            .is_some()
+    }
+
+    /// Keys under `[workspace.package]`, which members can inherit.
+    pub fn workspace_package_keys(&self) -> Vec<String> {
+        self.document
+            .get("workspace")
+            .and_then(|workspace| workspace.get("package"))
+            .and_then(|package| package.as_table_like())
+            .map(|package| package.iter().map(|(key, _)| key.to_string()).collect())
+            .unwrap_or_default()
+    }
+
+    /// A field under `[package]` given as a value, rather than inherited from the workspace.
+    pub fn package_value(&self, key: &str) -> Option<&toml_edit::Value> {
+        if self.inherits(key) {
+            return None;
+        }
+
+        self.document
+            .get("package")
+            .and_then(|package| package.get(key))
+            .and_then(Item::as_value)
+    }
+
+    /// Sets a field under `[package]`, returning whether it changed. A value that's already there
+    /// keeps its place and any comment after it.
+    pub fn set_package_value(
+        &mut self,
+        key: &str,
+        value: &toml_edit::Value,
+    ) -> Result<bool, GrumpyError> {
+        let package = self.table_mut(&["package"])?;
+
+        match package.get_mut(key).and_then(Item::as_value_mut) {
+            Some(existing) if same_value(existing, value) => return Ok(false),
+            Some(existing) => {
+                let decor = existing.decor().clone();
+                *existing = value.clone();
+                *existing.decor_mut() = decor;
+            }
+            None => {
+                package.insert(key, toml_edit::value(value.clone()));
+            }
+        }
+
+        Ok(true)
+    }
+
+    /// Makes a package field inherit its value from the workspace, as `key.workspace = true`.
+    pub fn inherit_package_field(&mut self, key: &str) -> Result<(), GrumpyError> {
+        let manifest_path = &self.path;
+
+        let package = self
+            .document
+            .entry("package")
+            .or_insert(toml_edit::table())
+            .as_table_mut()
+            .ok_or_else(|| GrumpyError::InvalidManifest {
+                path: manifest_path.clone(),
+                message: "[package] is not a table".to_string(),
+            })?;
+
+        let mut inherited = Table::new();
+        inherited.set_dotted(true);
+        inherited.insert("workspace", toml_edit::value(true));
+
+        package.insert(key, Item::Table(inherited));
+
+        Ok(())
+    }
+
+    /// Adds a path to `workspace.members`, returning whether it wasn't already listed.
+    pub fn add_workspace_member(&mut self, member: &str) -> Result<bool, GrumpyError> {
+        let manifest_path = &self.path;
+
+        let members = self
+            .document
+            .entry("workspace")
+            .or_insert(toml_edit::table())
+            .as_table_like_mut()
+            .and_then(|workspace| {
+                workspace
+                    .entry("members")
+                    .or_insert(toml_edit::value(toml_edit::Array::new()))
+                    .as_array_mut()
+            })
+            .ok_or_else(|| GrumpyError::InvalidManifest {
+                path: manifest_path.clone(),
+                message: "workspace.members is not an array".to_string(),
+            })?;
+
+        if members.iter().any(|listed| listed.as_str() == Some(member)) {
+            return Ok(false);
+        }
+
+        members.push(member);
+
+        Ok(true)
+    }
+
+    /// Package authors, if listed directly rather than inherited from a workspace.
+    pub fn authors(&self) -> Vec<String> {
+        self.document
+            .get("package")
+            .and_then(|package| package.get("authors"))
+            .and_then(|authors| authors.as_array())
+            .map(|authors| {
+                authors
+                    .iter()
+                    .filter_map(|author| author.as_str())
+                    .map(|author| author.to_string())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Adds a dependency, or updates the version of one that's already there. Entries written as
+    /// `name = "1.0"`, `name = { version = "1.0", ... }` and `[dependencies.name]` are all
+    /// understood, and keep their existing layout.
+    pub fn add_dependency(
+        &mut self,
+        dependency: &Dependency,
+    ) -> Result<DependencyChange, GrumpyError> {
+        let table = self.table_mut(&[dependency.kind.table_name()])?;
+
+        Ok(set_version(table, dependency))
+    }
+
+    /// Adds a dependency to `[workspace.dependencies]` for members to inherit, or updates the
+    /// version of one that's already there, in the same way as [`Manifest::add_dependency`].
+    pub fn add_workspace_dependency(
+        &mut self,
+        dependency: &Dependency,
+    ) -> Result<DependencyChange, GrumpyError> {
+        let table = self.table_mut(&["workspace", "dependencies"])?;
+
+        Ok(set_version(table, dependency))
+    }
+
+    /// Makes a dependency inherit from the workspace, as `name = { workspace = true }`. An entry
+    /// with a plain version is switched over, keeping any other keys such as `features`, while
+    /// path and git dependencies are left alone.
+    pub fn inherit_dependency(
+        &mut self,
+        dependency: &Dependency,
+    ) -> Result<DependencyChange, GrumpyError> {
+        let table = self.table_mut(&[dependency.kind.table_name()])?;
+
+        let entry = match table.entry(&dependency.name) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                entry.insert(toml_edit::value(inherited_dependency()));
+                return Ok(DependencyChange::Added);
+            }
+        };
+
+        if let Some(existing) = entry.as_value_mut().filter(|value| value.is_str()) {
+            let previous = existing.as_str().unwrap_or_default().to_string();
+
+            let decor = existing.decor().clone();
+            *existing = toml_edit::Value::InlineTable(inherited_dependency());
+            *existing.decor_mut() = decor;
+
+            return Ok(DependencyChange::Updated(previous));
+        }
+
+        let fields = match entry.as_table_like_mut() {
+            Some(fields) => fields,
+            None => return Ok(DependencyChange::Skipped),
+        };
+
+        if fields.get("workspace").and_then(Item::as_bool) == Some(true) {
+            return Ok(DependencyChange::Unchanged);
+        }
+
+        if fields.contains_key("path") || fields.contains_key("git") {
+            return Ok(DependencyChange::Skipped);
+        }
+
+        let previous = match fields.remove("version") {
+            Some(version) => version.as_str().unwrap_or_default().to_string(),
+            None => return Ok(DependencyChange::Skipped),
+        };
+
+        fields.insert("workspace", toml_edit::value(true));
+
+        // An inline table would otherwise keep the spacing that went with the version.
+        if let Some(fields) = entry.as_inline_table_mut() {
+            fields.fmt();
+        }
+
+        Ok(DependencyChange::Updated(previous))
+    }
+
+    /// Adds a dependency on a crate at a path relative to the package, as `name = { path = "..." }`.
+    /// An entry that already has a path or is inherited from the workspace is left as it is, and
+    /// any other entry is skipped, as it refers to some other crate of the same name.
+    pub fn add_path_dependency(
+        &mut self,
+        kind: DependencyKind,
+        name: &str,
+        path: &str,
+    ) -> Result<DependencyChange, GrumpyError> {
+        let table = self.table_mut(&[kind.table_name()])?;
+
+        Ok(set_path(table, name, path, true))
+    }
+
+    /// Adds a dependency on a crate at a path relative to the workspace to
+    /// `[workspace.dependencies]`, in the same way as [`Manifest::add_path_dependency`].
+    pub fn add_workspace_path_dependency(
+        &mut self,
+        name: &str,
+        path: &str,
+    ) -> Result<DependencyChange, GrumpyError> {
+        let table = self.table_mut(&["workspace", "dependencies"])?;
+
+        Ok(set_path(table, name, path, false))
+    }
+
+    /// Has a package inherit a dependency from the workspace, as `name = { workspace = true }`,
+    /// unless it already depends on it.
+    pub fn add_inherited_dependency(
+        &mut self,
+        kind: DependencyKind,
+        name: &str,
+    ) -> Result<DependencyChange, GrumpyError> {
+        let table = self.table_mut(&[kind.table_name()])?;
+
+        match table.entry(name) {
+            Entry::Occupied(entry) => Ok(existing_link(entry.get(), true)),
+            Entry::Vacant(entry) => {
+                entry.insert(toml_edit::value(inherited_dependency()));
+                Ok(DependencyChange::Added)
+            }
+        }
+    }
+
+    /// The table found by following `keys` from the top of the manifest, created if it's missing.
+    fn table_mut(&mut self, keys: &[&str]) -> Result<&mut dyn TableLike, GrumpyError> {
+        let path = &self.path;
+        let mut table: &mut dyn TableLike = self.document.as_table_mut();
+
+        for key in keys {
+            table = table
+                .entry(key)
+                .or_insert(toml_edit::table())
+                .as_table_like_mut()
+                .ok_or_else(|| GrumpyError::InvalidManifest {
+                    path: path.clone(),
+                    message: format!("[{}] is not a table", keys.join(".")),
+                })?;
+        }
+
+        Ok(table)
+    }
+
+    /// Removes a dependency, returning whether it was there to begin with.
+    pub fn remove_dependency(&mut self, kind: DependencyKind, name: &str) -> bool {
+        self.document
+            .get_mut(kind.table_name())
+            .and_then(|table| table.as_table_like_mut())
+            .and_then(|table| table.remove(name))
+            .is_some()
+    }
+}
+
+/// Adds a dependency to a dependency table, or updates the version of one that's already there.
+fn set_version(table: &mut dyn TableLike, dependency: &Dependency) -> DependencyChange {
+    let entry = match table.entry(&dependency.name) {
+        Entry::Occupied(entry) => entry.into_mut(),
+        Entry::Vacant(entry) => {
+            entry.insert(toml_edit::value(dependency.version.as_str()));
+            return DependencyChange::Added;
+        }
+    };
+
+    if let Some(existing) = entry.as_value_mut().filter(|value| value.is_str()) {
+        return replace_version(existing, &dependency.version);
+    }
+
+    match entry.get_mut("version").and_then(Item::as_value_mut) {
+        Some(existing) => replace_version(existing, &dependency.version),
+        None => DependencyChange::Skipped,
+    }
+}
+
+/// Adds a path dependency to a dependency table, unless there's an entry for it already.
+fn set_path(
+    table: &mut dyn TableLike,
+    name: &str,
+    path: &str,
+    may_inherit: bool,
+) -> DependencyChange {
+    match table.entry(name) {
+        Entry::Occupied(entry) => existing_link(entry.get(), may_inherit),
+        Entry::Vacant(entry) => {
+            let mut dependency = InlineTable::new();
+            dependency.insert("path", path.into());
+
+            entry.insert(toml_edit::value(dependency));
+            DependencyChange::Added
+        }
+    }
+}
+
+/// Whether an existing entry already links to a local crate, with a path or by inheriting it.
+fn existing_link(entry: &Item, may_inherit: bool) -> DependencyChange {
+    let inherited = may_inherit && entry.get("workspace").and_then(Item::as_bool) == Some(true);
+
+    if inherited || entry.get("path").is_some() {
+        DependencyChange::Unchanged
+    } else {
+        DependencyChange::Skipped
+    }
+}
+
+/// Whether two values are the same, however they're written.
+fn same_value(a: &toml_edit::Value, b: &toml_edit::Value) -> bool {
+    match (a, b) {
+        (toml_edit::Value::String(a), toml_edit::Value::String(b)) => a.value() == b.value(),
+        (toml_edit::Value::Array(a), toml_edit::Value::Array(b)) => {
+            a.len() == b.len() && a.iter().zip(b.iter()).all(|(a, b)| same_value(a, b))
+        }
+        _ => {
+            let mut a = a.clone();
+            let mut b = b.clone();
+            a.decor_mut().clear();
+            b.decor_mut().clear();
+
+            a.to_string() == b.to_string()
+        }
+    }
+}
+
+/// The entry for a dependency inherited from the workspace.
+fn inherited_dependency() -> InlineTable {
+    let mut table = InlineTable::new();
+    table.insert("workspace", true.into());
+
+    table
+}
+
+/// Swaps a version string for a new one, keeping any surrounding whitespace and comments.
+fn replace_version(existing: &mut toml_edit::Value, version: &str) -> DependencyChange {
+    let previous = existing.as_str().unwrap_or_default().to_string();
+
+    if previous == version {
+        return DependencyChange::Unchanged;
+    }
+
+    let decor = existing.decor().clone();
+    *existing = toml_edit::Value::from(version);
+    *existing.decor_mut() = decor;
+
+    DependencyChange::Updated(previous)
+}
+
+#[cfg(test)]
+impl fmt::Display for Manifest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.document)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dependency(spec: &str) -> Dependency {
+        Dependency::parse(spec).unwrap()
+    }
+
+    #[test]
+    fn parses_dependencies() {
+        assert_eq!(
+            dependency("serde_json@1.0"),
+            Dependency {
+                name: "serde_json".to_string(),
+                version: "1.0".to_string(),
+                kind: DependencyKind::Normal,
+            }
+        );
+        assert_eq!(dependency("dev:log@>=0.4, <0.5").kind, DependencyKind::Dev);
+        assert_eq!(dependency("build:cc@1").kind, DependencyKind::Build);
+        assert_eq!(dependency("dev:log@>=0.4, <0.5").version, ">=0.4, <0.5");
+    }
+
+    #[test]
+    fn refuses_malformed_dependencies() {
+        for spec in &[
+            "log",
+            "log@",
+            "@0.4",
+            "my log@0.4",
+            "log@0.4;",
+            "test:log@0.4",
+        ] {
+            assert!(Dependency::parse(spec).is_err(), "{} was accepted", spec);
+        }
+    }
+
+    #[test]
+    fn dependencies_display_as_parsed() {
+        for spec in &["log@0.4", "dev:log@0.4", "build:cc@^1.0"] {
+            assert_eq!(dependency(spec).to_string(), *spec);
+        }
+    }
+
+    #[test]
+    fn adds_and_updates_versions_in_place() {
+        let mut manifest = Manifest::parse(
+            "[dependencies]\nlog = \"0.3\" # logging\nserde = { version = \"1.0\", features = [\"derive\"] }\n\n[dependencies.anyhow]\nversion = \"1.0\"\n",
+        );
+
+        assert_eq!(
+            manifest.add_dependency(&dependency("log@0.4")).unwrap(),
+            DependencyChange::Updated("0.3".to_string())
+        );
+        assert_eq!(
+            manifest.add_dependency(&dependency("serde@1.0")).unwrap(),
+            DependencyChange::Unchanged
+        );
+        assert_eq!(
+            manifest.add_dependency(&dependency("anyhow@1.1")).unwrap(),
+            DependencyChange::Updated("1.0".to_string())
+        );
+        assert_eq!(
+            manifest.add_dependency(&dependency("dev:cc@1")).unwrap(),
+            DependencyChange::Added
+        );
+        assert_eq!(
+            manifest.to_string(),
+            "[dependencies]\nlog = \"0.4\" # logging\nserde = { version = \"1.0\", features = [\"derive\"] }\n\n[dependencies.anyhow]\nversion = \"1.1\"\n\n[dev-dependencies]\ncc = \"1\"\n"
+        );
+    }
+
+    #[test]
+    fn skips_dependencies_without_a_version() {
+        let mut manifest = Manifest::parse("[dependencies]\nlocal = { path = \"../local\" }\n");
+
+        assert_eq!(
+            manifest.add_dependency(&dependency("local@1.0")).unwrap(),
+            DependencyChange::Skipped
+        );
+    }
+
+    #[test]
+    fn removes_dependencies() {
+        let mut manifest = Manifest::parse("[dependencies]\nlog = \"0.4\"\n");
+
+        assert!(manifest.remove_dependency(DependencyKind::Normal, "log"));
+        assert!(!manifest.remove_dependency(DependencyKind::Normal, "log"));
+        assert!(!manifest.remove_dependency(DependencyKind::Dev, "log"));
+    }
+
+    #[test]
+    fn inherits_a_new_dependency() {
+        let mut manifest = Manifest::parse("[dependencies]\n");
+
+        assert_eq!(
+            manifest.inherit_dependency(&dependency("log@0.4")).unwrap(),
+            DependencyChange::Added
+        );
+        assert_eq!(
+            manifest.to_string(),
+            "[dependencies]\nlog = { workspace = true }\n"
+        );
+    }
+
+    #[test]
+    fn inherits_in_place_of_a_version() {
+        let mut manifest = Manifest::parse(
+            "[dependencies]\nlog = \"0.3\" # logging\nserde = { version = \"1.0\", features = [\"derive\"] }\n",
+        );
+
+        assert_eq!(
+            manifest.inherit_dependency(&dependency("log@0.4")).unwrap(),
+            DependencyChange::Updated("0.3".to_string())
+        );
+        assert_eq!(
+            manifest
+                .inherit_dependency(&dependency("serde@1.0"))
+                .unwrap(),
+            DependencyChange::Updated("1.0".to_string())
+        );
+        assert_eq!(
+            manifest.to_string(),
+            "[dependencies]\nlog = { workspace = true } # logging\nserde = { features = [\"derive\"], workspace = true }\n"
+        );
+    }
+
+    #[test]
+    fn leaves_inherited_path_and_git_dependencies() {
+        let contents = "[dependencies]\nlog = { workspace = true }\nlocal = { path = \"../local\" }\nremote = { git = \"https://example.com/remote\" }\n";
+        let mut manifest = Manifest::parse(contents);
+
+        assert_eq!(
+            manifest.inherit_dependency(&dependency("log@0.4")).unwrap(),
+            DependencyChange::Unchanged
+        );
+        assert_eq!(
+            manifest
+                .inherit_dependency(&dependency("local@1.0"))
+                .unwrap(),
+            DependencyChange::Skipped
+        );
+        assert_eq!(
+            manifest
+                .inherit_dependency(&dependency("remote@1.0"))
+                .unwrap(),
+            DependencyChange::Skipped
+        );
+        assert_eq!(manifest.to_string(), contents);
+    }
+
+    #[test]
+    fn links_by_path_once() {
+        let mut manifest =
+            Manifest::parse("[dependencies]\nlog = \"0.4\"\nshared = { workspace = true }\n");
+
+        assert_eq!(
+            manifest
+                .add_path_dependency(DependencyKind::Normal, "core", "../core")
+                .unwrap(),
+            DependencyChange::Added
+        );
+        assert_eq!(
+            manifest
+                .add_path_dependency(DependencyKind::Normal, "core", "../elsewhere")
+                .unwrap(),
+            DependencyChange::Unchanged
+        );
+        assert_eq!(
+            manifest
+                .add_path_dependency(DependencyKind::Normal, "shared", "../shared")
+                .unwrap(),
+            DependencyChange::Unchanged
+        );
+        assert_eq!(
+            manifest
+                .add_path_dependency(DependencyKind::Normal, "log", "../log")
+                .unwrap(),
+            DependencyChange::Skipped
+        );
+        assert!(manifest
+            .to_string()
+            .contains("core = { path = \"../core\" }\n"));
+    }
+
+    #[test]
+    fn workspace_path_dependencies_are_not_inherited() {
+        let mut manifest =
+            Manifest::parse("[workspace.dependencies]\ncore = { workspace = true }\n");
+
+        assert_eq!(
+            manifest
+                .add_workspace_path_dependency("core", "crates/core")
+                .unwrap(),
+            DependencyChange::Skipped
+        );
+        assert_eq!(
+            manifest
+                .add_workspace_path_dependency("util", "crates/util")
+                .unwrap(),
+            DependencyChange::Added
+        );
+    }
+
+    #[test]
+    fn members_inherit_linked_dependencies() {
+        let mut manifest = Manifest::parse("[dependencies]\n");
+
+        assert_eq!(
+            manifest
+                .add_inherited_dependency(DependencyKind::Normal, "core")
+                .unwrap(),
+            DependencyChange::Added
+        );
+        assert_eq!(
+            manifest
+                .add_inherited_dependency(DependencyKind::Normal, "core")
+                .unwrap(),
+            DependencyChange::Unchanged
+        );
+        assert_eq!(
+            manifest.dependency_packages(DependencyKind::Normal),
+            ["core"]
+        );
+    }
+
+    #[test]
+    fn renamed_dependencies_report_their_package() {
+        let manifest =
+            Manifest::parse("[dependencies]\nlogging = { package = \"log\", version = \"0.4\" }\n");
+
+        assert_eq!(
+            manifest.dependency_names(DependencyKind::Normal),
+            ["logging"]
+        );
+        assert_eq!(
+            manifest.dependency_packages(DependencyKind::Normal),
+            ["log"]
+        );
+    }
+
+    #[test]
+    fn workspace_dependencies_get_versions() {
+        let mut manifest = Manifest::parse("[workspace]\nmembers = []\n");
+
+        assert_eq!(
+            manifest
+                .add_workspace_dependency(&dependency("log@0.4"))
+                .unwrap(),
+            DependencyChange::Added
+        );
+        assert_eq!(
+            manifest
+                .add_workspace_dependency(&dependency("log@0.4"))
+                .unwrap(),
+            DependencyChange::Unchanged
+        );
+        assert_eq!(
+            manifest
+                .add_workspace_dependency(&dependency("log@0.5"))
+                .unwrap(),
+            DependencyChange::Updated("0.4".to_string())
+        );
+        assert!(manifest
+            .to_string()
+            .contains("[workspace.dependencies]\nlog = \"0.5\"\n"));
+    }
+}
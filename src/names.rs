@@ -0,0 +1,204 @@
+use crate::error::GrumpyError;
+
+/// Words Rust reserves, which can't name a crate.
+const KEYWORDS: &[&str] = &[
+    "abstract", "as", "async", "await", "become", "box", "break", "const", "continue", "crate",
+    "do", "dyn", "else", "enum", "extern", "false", "final", "fn", "for", "if", "impl", "in",
+    "let", "loop", "macro", "match", "mod", "move", "mut", "override", "priv", "pub", "ref",
+    "return", "self", "Self", "static", "struct", "super", "trait", "true", "try", "type",
+    "typeof", "unsafe", "unsized", "use", "virtual", "where", "while", "yield",
+];
+
+/// Crates that ship with Rust, which a package of the same name would shadow.
+const BUILTIN_CRATES: &[&str] = &["alloc", "core", "proc_macro", "proc-macro", "std", "test"];
+
+/// Directories cargo uses inside `target`, which would clash with a binary's output.
+const ARTIFACT_DIRS: &[&str] = &["build", "deps", "examples", "incremental"];
+
+/// Device names Windows won't allow as a file name, whatever the extension.
+const WINDOWS_DEVICES: &[&str] = &[
+    "aux", "com1", "com2", "com3", "com4", "com5", "com6", "com7", "com8", "com9", "con", "lpt1",
+    "lpt2", "lpt3", "lpt4", "lpt5", "lpt6", "lpt7", "lpt8", "lpt9", "nul", "prn",
+];
+
+/// Checks a name for `new` against the rules `cargo new` applies to package names.
+pub fn validate_project_name(name: &str) -> Result<(), GrumpyError> {
+    match problem(name) {
+        Some(reason) => Err(GrumpyError::InvalidName {
+            kind: "project",
+            name: name.to_string(),
+            reason,
+            suggestion: suggest(name),
+        }),
+        None => Ok(()),
+    }
+}
+
+/// Checks a script name, which may be given with or without `.rs`, and returns the binary name
+/// without it.
+pub fn script_name(name: &str) -> Result<String, GrumpyError> {
+    let bin_name = name.strip_suffix(".rs").unwrap_or(name);
+
+    match problem(bin_name) {
+        Some(reason) => Err(GrumpyError::InvalidName {
+            kind: "script",
+            name: name.to_string(),
+            reason,
+            suggestion: suggest(bin_name),
+        }),
+        None => Ok(bin_name.to_string()),
+    }
+}
+
+/// Says what's wrong with a name, if anything. Packages and binaries follow the same rules here,
+/// as each binary is built as a crate of its own.
+fn problem(name: &str) -> Option<String> {
+    let lower = name.to_lowercase();
+
+    if name.is_empty() {
+        Some("it's empty".to_string())
+    } else if name.contains('/') || name.contains('\\') {
+        Some("it contains a path separator".to_string())
+    } else if let Some(c) = name.chars().find(|c| !c.is_ascii()) {
+        Some(format!("{:?} isn't an ASCII character", c))
+    } else if let Some(c) = name
+        .chars()
+        .find(|c| !c.is_ascii_alphanumeric() && *c != '-' && *c != '_')
+    {
+        Some(format!(
+            "{:?} isn't allowed, only letters, numbers, - and _ are",
+            c
+        ))
+    } else if name.starts_with(|c: char| c.is_ascii_digit()) {
+        Some("it starts with a digit".to_string())
+    } else if name.starts_with('-') {
+        Some("it starts with -".to_string())
+    } else if KEYWORDS.contains(&name) {
+        Some("it's a Rust keyword".to_string())
+    } else if BUILTIN_CRATES.contains(&name) {
+        Some("it's the name of a crate that ships with Rust".to_string())
+    } else if ARTIFACT_DIRS.contains(&name) {
+        Some("cargo uses it for a build directory".to_string())
+    } else if WINDOWS_DEVICES.contains(&lower.as_str()) {
+        Some("it's a reserved file name on Windows".to_string())
+    } else {
+        None
+    }
+}
+
+/// Comes up with a valid name close to an invalid one. Accented letters lose their accents,
+/// other unusable characters become `-`, and reserved names get an `-rs` suffix.
+fn suggest(name: &str) -> Option<String> {
+    let mut suggestion = String::new();
+
+    for c in name.chars().map(strip_accent) {
+        if c.is_ascii_alphanumeric() || c == '_' {
+            suggestion.push(c);
+        } else if !suggestion.is_empty() && !suggestion.ends_with('-') {
+            suggestion.push('-');
+        }
+    }
+
+    let mut suggestion = suggestion.trim_end_matches('-').to_string();
+
+    if suggestion.is_empty() {
+        return None;
+    }
+
+    if suggestion.starts_with(|c: char| c.is_ascii_digit()) {
+        suggestion = format!("rs-{}", suggestion);
+    }
+
+    if problem(&suggestion).is_some() {
+        suggestion.push_str("-rs");
+    }
+
+    Some(suggestion).filter(|suggestion| problem(suggestion).is_none() && suggestion != name)
+}
+
+/// Maps the accented Latin letters most likely to turn up in a name onto their plain forms.
+fn strip_accent(c: char) -> char {
+    match c {
+        'à' | 'á' | 'â' | 'ã' | 'ä' | 'å' => 'a',
+        'À' | 'Á' | 'Â' | 'Ã' | 'Ä' | 'Å' => 'A',
+        'ç' => 'c',
+        'Ç' => 'C',
+        'è' | 'é' | 'ê' | 'ë' => 'e',
+        'È' | 'É' | 'Ê' | 'Ë' => 'E',
+        'ì' | 'í' | 'î' | 'ï' => 'i',
+        'Ì' | 'Í' | 'Î' | 'Ï' => 'I',
+        'ñ' => 'n',
+        'Ñ' => 'N',
+        'ò' | 'ó' | 'ô' | 'õ' | 'ö' | 'ø' => 'o',
+        'Ò' | 'Ó' | 'Ô' | 'Õ' | 'Ö' | 'Ø' => 'O',
+        'ù' | 'ú' | 'û' | 'ü' => 'u',
+        'Ù' | 'Ú' | 'Û' | 'Ü' => 'U',
+        'ý' | 'ÿ' => 'y',
+        'Ý' => 'Y',
+        _ => c,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accepts_ordinary_names() {
+        for name in &["grumpy", "cargo-grumpy", "my_tool2", "Tool"] {
+            assert_eq!(problem(name), None, "{} was refused", name);
+        }
+    }
+
+    #[test]
+    fn refuses_names_cargo_would() {
+        for name in &[
+            "",
+            "a/b",
+            "café",
+            "my tool",
+            "2fast",
+            "-tool",
+            "fn",
+            "Self",
+            "std",
+            "proc-macro",
+            "deps",
+            "CON",
+            "nul",
+        ] {
+            assert!(problem(name).is_some(), "{:?} was accepted", name);
+        }
+    }
+
+    #[test]
+    fn script_names_lose_their_extension() {
+        assert_eq!(script_name("tool.rs").unwrap(), "tool");
+        assert_eq!(script_name("tool").unwrap(), "tool");
+        assert!(script_name("fn.rs").is_err());
+    }
+
+    #[test]
+    fn suggests_names_that_would_work() {
+        assert_eq!(suggest("café au lait"), Some("cafe-au-lait".to_string()));
+        assert_eq!(suggest("2fast"), Some("rs-2fast".to_string()));
+        assert_eq!(suggest("fn"), Some("fn-rs".to_string()));
+        assert_eq!(suggest("con"), Some("con-rs".to_string()));
+        assert_eq!(suggest("!!!"), None);
+    }
+
+    #[test]
+    fn suggestions_are_valid() {
+        for name in &["my tool!", "été", "-leading", "test", "a--b"] {
+            if let Some(suggestion) = suggest(name) {
+                assert_eq!(
+                    problem(&suggestion),
+                    None,
+                    "{:?} from {:?}",
+                    suggestion,
+                    name
+                );
+            }
+        }
+    }
+}
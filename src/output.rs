@@ -0,0 +1,406 @@
+use crate::config::{Setting, Value};
+use crate::error::{Explanation, GrumpyError};
+use crate::list::HarnessStatus;
+use crate::manifest::{Dependency, DependencyKind};
+use crate::targets::Target;
+use serde_json::json;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+/// How results are reported, chosen with `--message-format`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MessageFormat {
+    /// Plain messages for people, on stdout.
+    Human,
+    /// One JSON object per line on stdout, each with a `reason` field, as cargo does. Anything
+    /// meant for people goes to stderr instead, so stdout can be parsed as it arrives.
+    Json,
+}
+
+impl FromStr for MessageFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "human" => Ok(MessageFormat::Human),
+            "json" => Ok(MessageFormat::Json),
+            _ => Err(format!(
+                "unknown message format {:?}, expected human or json",
+                s
+            )),
+        }
+    }
+}
+
+static FORMAT: OnceLock<MessageFormat> = OnceLock::new();
+
+/// Sets the message format for the rest of the run. Only the first call has any effect.
+pub fn set_format(format: MessageFormat) {
+    FORMAT.get_or_init(|| format);
+}
+
+pub fn format() -> MessageFormat {
+    FORMAT.get().copied().unwrap_or(MessageFormat::Human)
+}
+
+/// Something worth telling the caller about. Each event has a JSON form, and most have a human
+/// form too.
+pub enum Event<'a> {
+    FileCreated {
+        path: &'a Path,
+        dry_run: bool,
+    },
+    FileUpdated {
+        path: &'a Path,
+        dry_run: bool,
+    },
+    FileRemoved {
+        path: &'a Path,
+        dry_run: bool,
+    },
+    DirectoryCreated {
+        path: &'a Path,
+        dry_run: bool,
+    },
+    /// A file that was left alone rather than written, which is always followed by an error.
+    FileSkipped {
+        path: &'a Path,
+        message: &'a str,
+    },
+    CommandPlanned {
+        command: &'a str,
+    },
+    CargoCommand {
+        command: &'a str,
+        args: &'a [String],
+        exit_code: Option<u32>,
+    },
+    /// A dependency added to `table`, which is `[workspace.dependencies]` when a member inherits
+    /// it from there.
+    DependencyAdded {
+        dependency: &'a Dependency,
+        table: &'a str,
+    },
+    DependencyUpdated {
+        dependency: &'a Dependency,
+        previous: &'a str,
+        table: &'a str,
+    },
+    DependencyRemoved {
+        name: &'a str,
+        kind: DependencyKind,
+    },
+    DependencySkipped {
+        name: &'a str,
+        kind: DependencyKind,
+        message: String,
+    },
+    DependencyFailed {
+        dependency: &'a Dependency,
+        error: &'a GrumpyError,
+    },
+    /// One workspace member made to depend on another by `link`.
+    MemberLinked {
+        from: &'a str,
+        to: &'a str,
+        /// Where the dependency lives, relative to the workspace root.
+        path: &'a str,
+    },
+    /// A setting as listed by `config show`.
+    ConfigSetting {
+        key: &'a str,
+        setting: Option<&'a Setting>,
+    },
+    /// A single setting as shown by `config get`.
+    ConfigValue {
+        key: &'a str,
+        setting: Option<&'a Setting>,
+    },
+    ConfigUpdated {
+        key: &'a str,
+        location: &'a str,
+    },
+    /// A target as listed by `list`, with binaries' template status.
+    TargetListed {
+        target: &'a Target,
+        path: &'a str,
+        harness: Option<&'a HarnessStatus>,
+    },
+    /// An exit code, either as one line of the full list or explained in detail.
+    ExitCode {
+        explanation: &'a Explanation,
+        detailed: bool,
+    },
+    /// The outcome of the whole run, always the last event.
+    Finished {
+        error: Option<&'a GrumpyError>,
+    },
+}
+
+impl Event<'_> {
+    fn human(&self) -> Option<String> {
+        match self {
+            Event::DependencyAdded { dependency, table } => Some(format!(
+                "Added {} {} to [{}]",
+                dependency.name, dependency.version, table
+            )),
+            Event::DependencyUpdated {
+                dependency,
+                previous,
+                table,
+            } => Some(format!(
+                "Updated {} from {} to {} in [{}]",
+                dependency.name, previous, dependency.version, table
+            )),
+            Event::DependencyRemoved { name, kind } => {
+                Some(format!("Removed {} from [{}]", name, kind.table_name()))
+            }
+            Event::DependencySkipped { message, .. } => Some(message.clone()),
+            Event::DependencyFailed { dependency, error } => {
+                Some(format!("    {}: {}", dependency, error))
+            }
+            Event::MemberLinked { from, to, path } => {
+                Some(format!("Linked {} to {} at {}", from, to, path))
+            }
+            Event::ConfigSetting { key, setting } => Some(match setting {
+                Some(setting) => format!("{} = {} ({})", key, setting.value, setting.source),
+                None => format!("{} is not set", key),
+            }),
+            Event::ConfigValue { key, setting } => Some(match setting {
+                Some(setting) => format!("{} ({})", setting.value, setting.source),
+                None => format!("{} is not set", key),
+            }),
+            Event::ConfigUpdated { key, location } => Some(format!("Set {} in {}", key, location)),
+            Event::TargetListed {
+                target,
+                path,
+                harness,
+            } => Some(format!(
+                "{:<8} {:<24} {}{}",
+                target.kinds.join(","),
+                target.name,
+                path,
+                harness.map(harness_human).unwrap_or_default()
+            )),
+            Event::ExitCode {
+                explanation,
+                detailed: true,
+            } => Some(format!(
+                "{}: {}\n\n{}",
+                explanation.code, explanation.summary, explanation.help
+            )),
+            Event::ExitCode {
+                explanation,
+                detailed: false,
+            } => Some(format!("{:>3}  {}", explanation.code, explanation.summary)),
+            // File changes are shown as a diff by dry runs, cargo speaks for itself, and errors
+            // are printed by main.
+            _ => None,
+        }
+    }
+
+    fn json(&self) -> serde_json::Value {
+        match self {
+            Event::FileCreated { path, dry_run } => file_json("file-created", path, *dry_run),
+            Event::FileUpdated { path, dry_run } => file_json("file-updated", path, *dry_run),
+            Event::FileRemoved { path, dry_run } => file_json("file-removed", path, *dry_run),
+            Event::DirectoryCreated { path, dry_run } => {
+                file_json("directory-created", path, *dry_run)
+            }
+            Event::FileSkipped { path, message } => json!({
+                "reason": "file-skipped",
+                "path": path.to_string_lossy(),
+                "message": message,
+            }),
+            Event::CommandPlanned { command } => json!({
+                "reason": "command-planned",
+                "command": command,
+            }),
+            Event::CargoCommand {
+                command,
+                args,
+                exit_code,
+            } => json!({
+                "reason": "cargo-command",
+                "command": command,
+                "args": args,
+                "success": *exit_code == Some(0),
+                "exit_code": exit_code,
+            }),
+            Event::DependencyAdded { dependency, table } => {
+                let mut value = dependency_json("dependency-added", dependency);
+                value["table"] = json!(table);
+                value
+            }
+            Event::DependencyUpdated {
+                dependency,
+                previous,
+                table,
+            } => {
+                let mut value = dependency_json("dependency-updated", dependency);
+                value["previous_version"] = json!(previous);
+                value["table"] = json!(table);
+                value
+            }
+            Event::DependencyRemoved { name, kind } => json!({
+                "reason": "dependency-removed",
+                "name": name,
+                "kind": kind.metadata_kind(),
+            }),
+            Event::DependencySkipped {
+                name,
+                kind,
+                message,
+            } => json!({
+                "reason": "dependency-skipped",
+                "name": name,
+                "kind": kind.metadata_kind(),
+                "message": message,
+            }),
+            Event::DependencyFailed { dependency, error } => {
+                let mut value = dependency_json("dependency-failed", dependency);
+                value["message"] = json!(error.to_string());
+                value
+            }
+            Event::MemberLinked { from, to, path } => json!({
+                "reason": "member-linked",
+                "from": from,
+                "to": to,
+                "path": path,
+            }),
+            Event::ConfigSetting { key, setting } | Event::ConfigValue { key, setting } => {
+                json!({
+                    "reason": "config-setting",
+                    "key": key,
+                    "value": setting.map(|setting| match &setting.value {
+                        Value::Str(value) => json!(value),
+                        Value::Bool(value) => json!(value),
+                        Value::List(values) => json!(values),
+                    }),
+                    "source": setting.map(|setting| setting.source.to_string()),
+                })
+            }
+            Event::ConfigUpdated { key, location } => json!({
+                "reason": "config-updated",
+                "key": key,
+                "location": location,
+            }),
+            Event::TargetListed {
+                target,
+                path,
+                harness,
+            } => {
+                let mut value = json!({
+                    "reason": "target",
+                    "name": target.name,
+                    "kind": target.kinds,
+                    "src_path": path,
+                });
+
+                if let Some(harness) = harness {
+                    value["generated"] = json!(false);
+
+                    if let HarnessStatus::Generated {
+                        header,
+                        modified,
+                        template_changed,
+                    } = harness
+                    {
+                        value["generated"] = json!(true);
+                        value["template"] = json!(header.template);
+                        value["template_version"] = json!(header.version);
+                        value["modified"] = json!(modified);
+                        value["template_changed"] = json!(template_changed);
+                    }
+                }
+
+                value
+            }
+            Event::ExitCode { explanation, .. } => json!({
+                "reason": "exit-code",
+                "code": explanation.code,
+                "summary": explanation.summary,
+                "help": explanation.help,
+            }),
+            Event::Finished { error } => json!({
+                "reason": "run-finished",
+                "success": error.is_none(),
+                "exit_code": error.map_or(0, |error| error.exit_code()),
+                "message": error.map(|error| error.to_string()),
+            }),
+        }
+    }
+}
+
+/// Describes a binary's template status after its path, in `list`.
+fn harness_human(harness: &HarnessStatus) -> String {
+    match harness {
+        HarnessStatus::NotGenerated => "  (not from a template)".to_string(),
+        HarnessStatus::Generated {
+            header,
+            modified,
+            template_changed,
+        } => {
+            let mut status = vec![];
+
+            if *modified {
+                status.push("modified");
+            }
+
+            match template_changed {
+                Some(true) => status.push("template has changed"),
+                Some(false) => {}
+                None => status.push("template not found"),
+            }
+
+            if status.is_empty() {
+                status.push("unchanged");
+            }
+
+            format!(
+                "  ({} {}, {})",
+                header.template,
+                header.version.chars().take(8).collect::<String>(),
+                status.join(", ")
+            )
+        }
+    }
+}
+
+fn file_json(reason: &str, path: &Path, dry_run: bool) -> serde_json::Value {
+    json!({
+        "reason": reason,
+        "path": path.to_string_lossy(),
+        "dry_run": dry_run,
+    })
+}
+
+fn dependency_json(reason: &str, dependency: &Dependency) -> serde_json::Value {
+    json!({
+        "reason": reason,
+        "name": dependency.name,
+        "version": dependency.version,
+        "kind": dependency.kind.metadata_kind(),
+    })
+}
+
+/// Reports an event in the chosen message format.
+pub fn emit(event: Event) {
+    match format() {
+        MessageFormat::Human => {
+            if let Some(message) = event.human() {
+                println!("{}", message);
+            }
+        }
+        MessageFormat::Json => println!("{}", event.json()),
+    }
+}
+
+/// Prints a message meant only for people, which is kept off stdout when it's carrying JSON.
+pub fn note(message: &str) {
+    match format() {
+        MessageFormat::Human => println!("{}", message),
+        MessageFormat::Json => eprintln!("{}", message),
+    }
+}
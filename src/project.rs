@@ -0,0 +1,169 @@
+use crate::current_dir;
+use crate::error::{io_error, GrumpyError};
+use std::fs;
+use std::path::{Path, PathBuf};
+use toml_edit::DocumentMut;
+
+/// The package a command works on, found the way cargo finds it.
+pub struct Project {
+    /// Directory holding the package's Cargo.toml.
+    pub root: PathBuf,
+    /// Root of the workspace the package belongs to, if any. This is the package's own root when
+    /// its Cargo.toml has a `[workspace]` table too.
+    pub workspace_root: Option<PathBuf>,
+}
+
+impl Project {
+    /// Finds the project from `--project-name` or `--manifest-path` if given, or otherwise from
+    /// the nearest Cargo.toml at or above the current directory.
+    pub fn locate(
+        project_name: Option<&String>,
+        manifest_path: Option<&String>,
+    ) -> Result<Self, GrumpyError> {
+        let current_dir = current_dir()?;
+
+        let manifest_path = match (project_name, manifest_path) {
+            (Some(_), Some(_)) => {
+                return Err(GrumpyError::ConflictingOptions(
+                    "--project-name",
+                    "--manifest-path",
+                ))
+            }
+            (Some(project_name), None) => {
+                let manifest_path = current_dir.join(project_name).join("Cargo.toml");
+
+                if !manifest_path.is_file() {
+                    // Most likely the name of the project we're already in, rather than one
+                    // below it.
+                    if find_manifest(&current_dir).is_some() {
+                        return Err(GrumpyError::ProjectNameInsideProject);
+                    }
+
+                    return Err(GrumpyError::ManifestNotFound {
+                        path: manifest_path,
+                    });
+                }
+
+                manifest_path
+            }
+            (None, Some(manifest_path)) => {
+                let manifest_path = current_dir.join(manifest_path);
+
+                if manifest_path
+                    .file_name()
+                    .is_none_or(|name| name != "Cargo.toml")
+                    || !manifest_path.is_file()
+                {
+                    return Err(GrumpyError::ManifestNotFound {
+                        path: manifest_path,
+                    });
+                }
+
+                manifest_path
+            }
+            (None, None) => find_manifest(&current_dir).ok_or(GrumpyError::NoProjectName)?,
+        };
+
+        let document = read_manifest(&manifest_path)?;
+
+        if document.get("package").is_none() {
+            // A virtual manifest only lists workspace members, so there's no package to change.
+            return Err(GrumpyError::VirtualManifest {
+                path: manifest_path,
+            });
+        }
+
+        let root = manifest_path
+            .parent()
+            .map(Path::to_path_buf)
+            .unwrap_or(current_dir);
+
+        let workspace_root = workspace_root(&root, &document)?;
+
+        Ok(Project {
+            root,
+            workspace_root,
+        })
+    }
+}
+
+/// Finds the root of the workspace to work on, from `--manifest-path` if given, or otherwise the
+/// workspace of the nearest Cargo.toml at or above the current directory.
+pub fn locate_workspace(manifest_path: Option<&String>) -> Result<PathBuf, GrumpyError> {
+    let current_dir = current_dir()?;
+
+    let manifest_path = match manifest_path {
+        Some(manifest_path) => current_dir.join(manifest_path),
+        None => find_manifest(&current_dir).ok_or_else(|| GrumpyError::NotInWorkspace {
+            path: current_dir.clone(),
+        })?,
+    };
+
+    if !manifest_path.is_file() {
+        return Err(GrumpyError::ManifestNotFound {
+            path: manifest_path,
+        });
+    }
+
+    let root = manifest_path
+        .parent()
+        .map(Path::to_path_buf)
+        .unwrap_or(current_dir);
+
+    workspace_root(&root, &read_manifest(&manifest_path)?)?
+        .ok_or(GrumpyError::NotInWorkspace { path: root })
+}
+
+/// The directory of the nearest Cargo.toml at or above `start`, if there is one.
+pub fn find_root(start: &Path) -> Option<PathBuf> {
+    find_manifest(start).and_then(|manifest_path| manifest_path.parent().map(Path::to_path_buf))
+}
+
+fn find_manifest(start: &Path) -> Option<PathBuf> {
+    start
+        .ancestors()
+        .map(|dir| dir.join("Cargo.toml"))
+        .find(|manifest_path| manifest_path.is_file())
+}
+
+fn read_manifest(manifest_path: &Path) -> Result<DocumentMut, GrumpyError> {
+    fs::read_to_string(manifest_path)
+        .map_err(io_error("read", manifest_path))?
+        .parse::<DocumentMut>()
+        .map_err(|e| GrumpyError::InvalidManifest {
+            path: manifest_path.to_path_buf(),
+            message: e.to_string(),
+        })
+}
+
+/// Works out which workspace a package belongs to. As with cargo, `package.workspace` names the
+/// root directly, and otherwise it's the nearest Cargo.toml above the package with a
+/// `[workspace]` table.
+fn workspace_root(root: &Path, document: &DocumentMut) -> Result<Option<PathBuf>, GrumpyError> {
+    if document.get("workspace").is_some() {
+        return Ok(Some(root.to_path_buf()));
+    }
+
+    if let Some(workspace) = document
+        .get("package")
+        .and_then(|package| package.get("workspace"))
+        .and_then(|workspace| workspace.as_str())
+    {
+        return Ok(Some(root.join(workspace)));
+    }
+
+    enclosing_workspace(root)
+}
+
+/// The nearest directory above `root` whose Cargo.toml has a `[workspace]` table, if any.
+pub fn enclosing_workspace(root: &Path) -> Result<Option<PathBuf>, GrumpyError> {
+    for dir in root.ancestors().skip(1) {
+        let manifest_path = dir.join("Cargo.toml");
+
+        if manifest_path.is_file() && read_manifest(&manifest_path)?.get("workspace").is_some() {
+            return Ok(Some(dir.to_path_buf()));
+        }
+    }
+
+    Ok(None)
+}
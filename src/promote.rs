@@ -0,0 +1,108 @@
+use crate::convert::declares_file_modules;
+use crate::error::{io_error, GrumpyError};
+use crate::manifest::Manifest;
+use crate::names;
+use crate::output;
+use crate::project::Project;
+use crate::targets::{self, Targets};
+use crate::transaction::Transaction;
+use argh::FromArgs;
+use std::path::Path;
+
+#[derive(FromArgs, PartialEq, Debug)]
+/// move a single-file binary to the src/bin/<name>/main.rs layout
+#[argh(subcommand, name = "promote")]
+pub struct PromoteSubCommand {
+    /// name of project
+    #[argh(option, short = 'p')]
+    project_name: Option<String>,
+
+    /// path to the project's Cargo.toml
+    #[argh(option)]
+    manifest_path: Option<String>,
+
+    /// the binary to promote
+    #[argh(positional)]
+    script_name: String,
+}
+
+/// Moves `<dir>/<name>.rs` to `<dir>/<name>/main.rs`, so that modules can be added next to it.
+/// Any `[[bin]]` table naming the old path is pointed at the new one.
+pub fn promote_binary(
+    transaction: &mut Transaction,
+    project_root: &Path,
+    bin_name: &str,
+) -> Result<(), GrumpyError> {
+    let targets = Targets::load(transaction, project_root)?;
+
+    let src_path = targets
+        .bin(bin_name)
+        .map(|bin| bin.src_path.clone())
+        .ok_or_else(|| GrumpyError::BinNotFound {
+            name: bin_name.to_string(),
+        })?;
+
+    if src_path.file_name().is_some_and(|name| name == "main.rs") {
+        return Err(GrumpyError::UnsupportedLayout {
+            path: src_path,
+            message: "it's already a main.rs, so there's nothing to promote".to_string(),
+        });
+    }
+
+    let source = transaction
+        .read(&src_path)
+        .map_err(io_error("read", &src_path))?;
+
+    // Modules in other files are found relative to the binary, so they'd be lost by the move.
+    if declares_file_modules(&String::from_utf8_lossy(&source)) {
+        return Err(GrumpyError::UnsupportedLayout {
+            path: src_path,
+            message: "it declares modules in other files, which would need to move too".to_string(),
+        });
+    }
+
+    let bin_dir = src_path.with_extension("");
+    let main_path = bin_dir.join("main.rs");
+
+    if transaction.exists(&bin_dir) {
+        return Err(GrumpyError::AlreadyExists { path: bin_dir });
+    }
+
+    transaction
+        .write(&main_path, &source)
+        .map_err(io_error("write", &main_path))?;
+    transaction
+        .remove_file(&src_path)
+        .map_err(io_error("remove", &src_path))?;
+
+    let mut manifest = Manifest::load(transaction, project_root)?;
+    let relative_path = targets::manifest_relative(project_root, &main_path);
+
+    if manifest.set_bin_path(bin_name, &relative_path) {
+        manifest.save(transaction)?;
+    }
+
+    output::note(&format!(
+        "Moved {} to {}",
+        src_path.display(),
+        main_path.display()
+    ));
+
+    Ok(())
+}
+
+pub fn process_promote(promote_args: &PromoteSubCommand, dry_run: bool) -> Result<(), GrumpyError> {
+    let project = Project::locate(
+        promote_args.project_name.as_ref(),
+        promote_args.manifest_path.as_ref(),
+    )?;
+    let bin_name = names::script_name(&promote_args.script_name)?;
+
+    let mut transaction = Transaction::new(dry_run);
+
+    promote_binary(&mut transaction, &project.root, &bin_name)?;
+
+    transaction.commit();
+
+    Ok(())
+}
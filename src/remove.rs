@@ -0,0 +1,216 @@
+use crate::confirm;
+use crate::dep;
+use crate::error::{io_error, GrumpyError};
+use crate::manifest::{DependencyKind, Manifest};
+use crate::names;
+use crate::output;
+use crate::project::Project;
+use crate::targets::Targets;
+use crate::transaction::Transaction;
+use argh::FromArgs;
+use std::fs;
+use std::path::Path;
+
+#[derive(FromArgs, PartialEq, Debug)]
+/// remove a binary, along with its [[bin]] table and any dependencies only it used
+#[argh(subcommand, name = "remove")]
+pub struct RemoveSubCommand {
+    /// name of project
+    #[argh(option, short = 'p')]
+    project_name: Option<String>,
+
+    /// path to the project's Cargo.toml
+    #[argh(option)]
+    manifest_path: Option<String>,
+
+    /// the binary to remove
+    #[argh(positional)]
+    script_name: String,
+
+    /// remove dependencies that nothing else uses without asking
+    #[argh(switch)]
+    drop_deps: bool,
+}
+
+/// Deletes a binary's source, and any `[[bin]]` table for it. A binary laid out as
+/// `src/bin/<name>/main.rs` takes its whole directory with it.
+///
+/// Returns the dependencies the binary used that nothing left in the package does.
+pub fn remove_binary(
+    transaction: &mut Transaction,
+    project_root: &Path,
+    bin_name: &str,
+) -> Result<Vec<String>, GrumpyError> {
+    let targets = Targets::load(transaction, project_root)?;
+
+    let src_path = targets
+        .bin(bin_name)
+        .map(|bin| bin.src_path.clone())
+        .ok_or_else(|| GrumpyError::BinNotFound {
+            name: bin_name.to_string(),
+        })?;
+
+    if targets.bins().len() == 1 && !targets.has_lib() {
+        return Err(GrumpyError::UnsupportedLayout {
+            path: src_path,
+            message: "it's the package's only target, so there'd be nothing left to build"
+                .to_string(),
+        });
+    }
+
+    let bin_dir = src_path
+        .parent()
+        .filter(|dir| {
+            src_path.file_name().is_some_and(|name| name == "main.rs")
+                && dir
+                    .parent()
+                    .is_some_and(|parent| parent.ends_with("src/bin"))
+        })
+        .map(Path::to_path_buf);
+
+    let mut removed_sources = vec![];
+    read_sources(
+        transaction,
+        bin_dir.as_ref().unwrap_or(&src_path),
+        &mut removed_sources,
+    )?;
+
+    match &bin_dir {
+        Some(bin_dir) => {
+            transaction
+                .remove_dir_all(bin_dir)
+                .map_err(io_error("remove", bin_dir))?;
+            output::note(&format!("Removed {}", bin_dir.display()));
+        }
+        None => {
+            transaction
+                .remove_file(&src_path)
+                .map_err(io_error("remove", &src_path))?;
+            output::note(&format!("Removed {}", src_path.display()));
+        }
+    }
+
+    let mut manifest = Manifest::load(transaction, project_root)?;
+
+    if manifest.remove_bin_target(bin_name) {
+        manifest.save(transaction)?;
+    }
+
+    // Everything left that could use a normal dependency. Targets with custom paths are read
+    // directly, as they may be outside these directories.
+    let mut remaining_sources = vec![];
+
+    for dir in &["src", "tests", "examples", "benches"] {
+        read_sources(transaction, &project_root.join(dir), &mut remaining_sources)?;
+    }
+
+    for target in targets.lib().into_iter().chain(targets.bins()) {
+        read_sources(transaction, &target.src_path, &mut remaining_sources)?;
+    }
+
+    Ok(manifest
+        .dependency_names(DependencyKind::Normal)
+        .into_iter()
+        .filter(|name| {
+            removed_sources
+                .iter()
+                .any(|source| references(source, name))
+                && !remaining_sources
+                    .iter()
+                    .any(|source| references(source, name))
+                && !manifest.features_use(name)
+        })
+        .collect())
+}
+
+/// Reads a Rust source file, or every one in a directory and those below it, skipping anything
+/// already removed.
+fn read_sources(
+    transaction: &Transaction,
+    path: &Path,
+    sources: &mut Vec<String>,
+) -> Result<(), GrumpyError> {
+    if !transaction.exists(path) {
+        return Ok(());
+    }
+
+    if transaction.is_dir(path) {
+        if !path.is_dir() {
+            // Only planned by a dry run, so there's nothing on disk to list.
+            return Ok(());
+        }
+
+        for entry in fs::read_dir(path).map_err(io_error("read", path))? {
+            let entry = entry.map_err(io_error("read", path))?;
+
+            read_sources(transaction, &entry.path(), sources)?;
+        }
+    } else if path.extension().is_some_and(|extension| extension == "rs") {
+        sources.push(
+            transaction
+                .read_to_string(path)
+                .map_err(io_error("read", path))?,
+        );
+    }
+
+    Ok(())
+}
+
+/// Whether source code refers to a dependency by path, as in `use name::item`, `name::item` or
+/// `extern crate name`. Anything that merely looks like one counts, so as to err on the side of
+/// keeping dependencies.
+fn references(source: &str, dependency: &str) -> bool {
+    let ident = dependency.replace('-', "_");
+    let is_ident = |c: char| c.is_alphanumeric() || c == '_';
+
+    source.match_indices(&ident).any(|(start, _)| {
+        let before = source[..start].trim_end_matches(' ');
+        let after = &source[start + ident.len()..];
+
+        if source[..start].ends_with(is_ident) || after.starts_with(is_ident) {
+            return false;
+        }
+
+        after.trim_start().starts_with("::")
+            || before.ends_with("use")
+            || before.ends_with("extern crate")
+    })
+}
+
+pub fn process_remove(remove_args: &RemoveSubCommand, dry_run: bool) -> Result<(), GrumpyError> {
+    let project = Project::locate(
+        remove_args.project_name.as_ref(),
+        remove_args.manifest_path.as_ref(),
+    )?;
+    let bin_name = names::script_name(&remove_args.script_name)?;
+
+    let mut transaction = Transaction::new(dry_run);
+
+    let unused = remove_binary(&mut transaction, &project.root, &bin_name)?;
+
+    if !unused.is_empty() {
+        let question = format!(
+            "Remove {}, which nothing else in the package uses?",
+            unused.join(", ")
+        );
+
+        // A dry run doesn't ask, as nothing it answers would be kept anyway.
+        if remove_args.drop_deps || (!dry_run && confirm(&question)) {
+            dep::remove_dependencies(
+                &mut transaction,
+                &project.root,
+                DependencyKind::Normal,
+                &unused,
+            )?;
+        } else {
+            output::note(&format!(
+                "Keeping {}, which nothing else in the package uses",
+                unused.join(", ")
+            ));
+        }
+    }
+
+    transaction.commit();
+
+    Ok(())
+}
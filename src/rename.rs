@@ -0,0 +1,330 @@
+use crate::error::{io_error, GrumpyError};
+use crate::manifest::Manifest;
+use crate::names;
+use crate::output;
+use crate::project::Project;
+use crate::targets::{manifest_relative, Targets};
+use crate::template::GENERATED_MARKER;
+use crate::transaction::Transaction;
+use argh::FromArgs;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(FromArgs, PartialEq, Debug)]
+/// rename a binary, along with its [[bin]] table and generated files that mention it
+#[argh(subcommand, name = "rename")]
+pub struct RenameSubCommand {
+    /// name of project
+    #[argh(option, short = 'p')]
+    project_name: Option<String>,
+
+    /// path to the project's Cargo.toml
+    #[argh(option)]
+    manifest_path: Option<String>,
+
+    /// the binary to rename
+    #[argh(positional)]
+    old_name: String,
+
+    /// its new name
+    #[argh(positional)]
+    new_name: String,
+}
+
+/// Renames a binary. A source named after the binary, as `src/bin/<name>.rs` or
+/// `src/bin/<name>/main.rs`, is moved to match, and Cargo.toml is updated wherever it names the
+/// binary or its source. A binary built from a file not named after it, such as `src/main.rs`,
+/// is renamed with a `[[bin]]` table instead.
+pub fn rename_binary(
+    transaction: &mut Transaction,
+    project_root: &Path,
+    old_name: &str,
+    new_name: &str,
+) -> Result<(), GrumpyError> {
+    let targets = Targets::load(transaction, project_root)?;
+
+    let src_path = targets
+        .bin(old_name)
+        .map(|bin| bin.src_path.clone())
+        .ok_or_else(|| GrumpyError::BinNotFound {
+            name: old_name.to_string(),
+        })?;
+
+    if let Some(existing) = targets.bin(new_name) {
+        return Err(GrumpyError::AlreadyExists {
+            path: existing.src_path.clone(),
+        });
+    }
+
+    let bin_dir = src_path.parent().unwrap_or(project_root).to_path_buf();
+    let in_bin_dir = bin_dir.ends_with("src/bin");
+
+    let new_path = if in_bin_dir && src_path.file_stem().is_some_and(|stem| stem == old_name) {
+        let new_path = bin_dir.join(format!("{}.rs", new_name));
+
+        check_free(transaction, &bin_dir, new_name)?;
+
+        let source = transaction
+            .read(&src_path)
+            .map_err(io_error("read", &src_path))?;
+
+        transaction
+            .write(&new_path, &source)
+            .map_err(io_error("write", &new_path))?;
+        transaction
+            .remove_file(&src_path)
+            .map_err(io_error("remove", &src_path))?;
+
+        new_path
+    } else if bin_dir.parent().is_some_and(|dir| dir.ends_with("src/bin"))
+        && bin_dir.file_name().is_some_and(|dir| dir == old_name)
+        && src_path.file_name().is_some_and(|file| file == "main.rs")
+    {
+        let parent = bin_dir.parent().unwrap_or(project_root);
+        let new_dir = parent.join(new_name);
+
+        check_free(transaction, parent, new_name)?;
+        copy_tree(transaction, &bin_dir, &new_dir)?;
+
+        transaction
+            .remove_dir_all(&bin_dir)
+            .map_err(io_error("remove", &bin_dir))?;
+
+        new_dir.join("main.rs")
+    } else {
+        src_path.clone()
+    };
+
+    let mut manifest = Manifest::load(transaction, project_root)?;
+    let relative_path = manifest_relative(project_root, &new_path);
+
+    if !manifest.rename_bin_target(old_name, new_name, &relative_path) && new_path == src_path {
+        // cargo named the binary after the package or its file, so only a table can rename it.
+        let autobins = manifest.autobins();
+
+        manifest.add_bin_target(new_name, &relative_path)?;
+
+        // In the 2015 edition the first table stops cargo finding src/bin by itself, which would
+        // lose every other binary there.
+        if autobins && !manifest.autobins() {
+            manifest.set_package_value("autobins", &toml_edit::Value::from(true))?;
+        }
+    }
+
+    manifest.save(transaction)?;
+
+    if new_path != src_path {
+        output::note(&format!(
+            "Moved {} to {}",
+            src_path.display(),
+            new_path.display()
+        ));
+    }
+
+    for path in generated_files(project_root) {
+        rewrite_generated(transaction, &path, old_name, new_name)?;
+    }
+
+    Ok(())
+}
+
+/// Makes sure neither layout of the new binary is already taken, as cargo refuses to build a
+/// binary found in both.
+fn check_free(transaction: &Transaction, dir: &Path, new_name: &str) -> Result<(), GrumpyError> {
+    for path in &[dir.join(format!("{}.rs", new_name)), dir.join(new_name)] {
+        if transaction.exists(path) {
+            return Err(GrumpyError::AlreadyExists { path: path.clone() });
+        }
+    }
+
+    Ok(())
+}
+
+/// Copies every file in a directory, and those below it, to the same place under another.
+fn copy_tree(transaction: &mut Transaction, from: &Path, to: &Path) -> Result<(), GrumpyError> {
+    for entry in fs::read_dir(from).map_err(io_error("read", from))? {
+        let entry = entry.map_err(io_error("read", from))?;
+        let path = entry.path();
+        let target = to.join(entry.file_name());
+
+        if path.is_dir() {
+            copy_tree(transaction, &path, &target)?;
+        } else if transaction.exists(&path) {
+            let contents = transaction.read(&path).map_err(io_error("read", &path))?;
+
+            transaction
+                .write(&target, &contents)
+                .map_err(io_error("write", &target))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// The README and CI files a project might have had generated for it, which refer to binaries by
+/// name.
+pub fn generated_files(project_root: &Path) -> Vec<PathBuf> {
+    let mut paths = vec![
+        project_root.join("README.md"),
+        project_root.join(".gitlab-ci.yml"),
+    ];
+
+    if let Ok(entries) = fs::read_dir(project_root.join(".github").join("workflows")) {
+        let mut workflows: Vec<PathBuf> = entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| {
+                path.extension()
+                    .is_some_and(|extension| extension == "yml" || extension == "yaml")
+            })
+            .collect();
+
+        workflows.sort();
+        paths.extend(workflows);
+    }
+
+    paths
+}
+
+/// Replaces the old name with the new one in a file cargo-grumpy generated. Files without the
+/// generated marker were written by someone else, so are left alone.
+fn rewrite_generated(
+    transaction: &mut Transaction,
+    path: &Path,
+    old_name: &str,
+    new_name: &str,
+) -> Result<(), GrumpyError> {
+    if !transaction.exists(path) {
+        return Ok(());
+    }
+
+    let contents = transaction
+        .read_to_string(path)
+        .map_err(io_error("read", path))?;
+
+    if !contents.contains(GENERATED_MARKER) {
+        return Ok(());
+    }
+
+    let rewritten = replace_name(&contents, old_name, new_name);
+
+    if rewritten != contents {
+        transaction
+            .write(path, rewritten.as_bytes())
+            .map_err(io_error("write", path))?;
+    }
+
+    Ok(())
+}
+
+/// Replaces references to a binary: `--bin <name>` and `src/bin/<name>`. Renaming `app` leaves
+/// `--bin app-server` alone, along with any other mention of the name, which may well be of the
+/// project rather than the binary.
+fn replace_name(text: &str, old_name: &str, new_name: &str) -> String {
+    let is_name = |c: char| c.is_ascii_alphanumeric() || c == '_' || c == '-';
+
+    let mut text = text.to_string();
+
+    for prefix in &["--bin ", "src/bin/"] {
+        let old_reference = format!("{}{}", prefix, old_name);
+
+        let mut replaced = String::new();
+        let mut copied = 0;
+
+        for (start, _) in text.match_indices(&old_reference) {
+            let end = start + old_reference.len();
+
+            if text[end..].starts_with(is_name) {
+                continue;
+            }
+
+            replaced.push_str(&text[copied..start]);
+            replaced.push_str(prefix);
+            replaced.push_str(new_name);
+            copied = end;
+        }
+
+        replaced.push_str(&text[copied..]);
+        text = replaced;
+    }
+
+    text
+}
+
+pub fn process_rename(rename_args: &RenameSubCommand, dry_run: bool) -> Result<(), GrumpyError> {
+    let project = Project::locate(
+        rename_args.project_name.as_ref(),
+        rename_args.manifest_path.as_ref(),
+    )?;
+    let old_name = names::script_name(&rename_args.old_name)?;
+    let new_name = names::script_name(&rename_args.new_name)?;
+
+    let mut transaction = Transaction::new(dry_run);
+
+    rename_binary(&mut transaction, &project.root, &old_name, &new_name)?;
+
+    transaction.commit();
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replaces_whole_names() {
+        assert_eq!(
+            replace_name("cargo run --bin app\nsrc/bin/app.rs\n", "app", "tool"),
+            "cargo run --bin tool\nsrc/bin/tool.rs\n"
+        );
+    }
+
+    #[test]
+    fn leaves_longer_names_alone() {
+        assert_eq!(
+            replace_name("--bin app-server src/bin/app_2.rs", "app", "tool"),
+            "--bin app-server src/bin/app_2.rs"
+        );
+    }
+
+    #[test]
+    fn leaves_other_mentions_alone() {
+        assert_eq!(
+            replace_name(
+                "# app\nRun app with `cargo run --bin app`.\n",
+                "app",
+                "tool"
+            ),
+            "# app\nRun app with `cargo run --bin tool`.\n"
+        );
+    }
+
+    #[test]
+    fn text_without_the_name_is_unchanged() {
+        assert_eq!(replace_name("nothing here", "app", "tool"), "nothing here");
+    }
+
+    #[test]
+    fn renaming_by_table_keeps_2015_autobins() {
+        let mut transaction = Transaction::new(true);
+        let root = PathBuf::from("/nonexistent/cargo-grumpy-test");
+
+        for (path, contents) in &[
+            ("Cargo.toml", "[package]\nname = \"demo\"\n"),
+            ("src/main.rs", ""),
+            ("src/bin/other.rs", ""),
+        ] {
+            transaction
+                .write(&root.join(path), contents.as_bytes())
+                .unwrap();
+        }
+
+        rename_binary(&mut transaction, &root, "demo", "tool").unwrap();
+
+        let targets = Targets::load(&transaction, &root).unwrap();
+        let names: Vec<&str> = targets.bins().iter().map(|bin| bin.name.as_str()).collect();
+
+        assert_eq!(names, ["tool", "other"]);
+    }
+}
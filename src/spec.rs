@@ -0,0 +1,354 @@
+use crate::error::{io_error, GrumpyError};
+use crate::names;
+use crate::targets::Layout;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use toml_edit::{Array, ArrayOfTables, DocumentMut, Item, Table, Value};
+
+/// Name of the file declaring a project's shape, at the root of the package.
+pub const SPEC_FILE: &str = "grumpy.toml";
+
+/// A binary the project should have.
+pub struct BinSpec {
+    pub name: String,
+    pub template: Option<String>,
+    pub layout: Layout,
+}
+
+/// A file the project should have, such as a default config file.
+pub struct FileSpec {
+    /// Relative to the package root.
+    pub path: String,
+    pub contents: String,
+}
+
+/// What a project should look like, as declared in `grumpy.toml`:
+///
+/// ```toml
+/// lib = true
+///
+/// [package]
+/// description = "Does the thing"
+/// license = "MIT"
+///
+/// [dependencies]
+/// profiles = ["cli"]
+///
+/// [[bin]]
+/// name = "my-tool"
+/// template = "harness"
+/// layout = "dir"
+///
+/// [[file]]
+/// path = "config/default.toml"
+/// contents = "level = \"info\"\n"
+/// ```
+pub struct Spec {
+    /// Fields to set under `[package]` in Cargo.toml.
+    pub package: Vec<(String, Value)>,
+    pub lib: bool,
+    pub bins: Vec<BinSpec>,
+    /// Dependency profiles whose crates the project should depend on.
+    pub profiles: Vec<String>,
+    pub files: Vec<FileSpec>,
+}
+
+impl Spec {
+    pub fn load(path: &Path) -> Result<Self, GrumpyError> {
+        let contents = fs::read_to_string(path).map_err(io_error("read", path))?;
+
+        Spec::parse(path, &contents)
+    }
+
+    /// Reads a spec from what's in its file, with `path` only used to say where errors are.
+    fn parse(path: &Path, contents: &str) -> Result<Self, GrumpyError> {
+        let invalid = |message: String| GrumpyError::InvalidSpec {
+            path: path.to_path_buf(),
+            message,
+        };
+
+        let document = contents
+            .parse::<DocumentMut>()
+            .map_err(|e| invalid(format!("Unable to parse TOML: {}", e)))?;
+
+        let mut spec = Spec {
+            package: vec![],
+            lib: false,
+            bins: vec![],
+            profiles: vec![],
+            files: vec![],
+        };
+
+        for (key, item) in document.iter() {
+            match key {
+                "lib" => {
+                    spec.lib = item
+                        .as_bool()
+                        .ok_or_else(|| invalid("lib must be true or false".to_string()))?;
+                }
+                "package" => {
+                    let package = item
+                        .as_table_like()
+                        .ok_or_else(|| invalid("[package] must be a table".to_string()))?;
+
+                    for (field, value) in package.iter() {
+                        let mut value = value.as_value().cloned().ok_or_else(|| {
+                            invalid(format!("package.{} must be a value, not a table", field))
+                        })?;
+
+                        value.decor_mut().clear();
+                        spec.package.push((field.to_string(), value));
+                    }
+                }
+                "dependencies" => {
+                    let dependencies = item
+                        .as_table()
+                        .ok_or_else(|| invalid("[dependencies] must be a table".to_string()))?;
+
+                    check_keys(dependencies, "dependencies", &["profiles"]).map_err(invalid)?;
+
+                    spec.profiles = string_list(dependencies.get("profiles"))
+                        .ok_or_else(|| invalid("dependencies.profiles must be a list".into()))?;
+                }
+                "bin" => {
+                    for table in tables(item, "bin").map_err(invalid)? {
+                        let bin = bin_spec(table).map_err(invalid)?;
+
+                        if spec.bins.iter().any(|other| other.name == bin.name) {
+                            return Err(invalid(format!("More than one [[bin]] is {}", bin.name)));
+                        }
+
+                        spec.bins.push(bin);
+                    }
+                }
+                "file" => {
+                    for table in tables(item, "file").map_err(invalid)? {
+                        spec.files.push(file_spec(table).map_err(invalid)?);
+                    }
+                }
+                _ => return Err(invalid(format!("Unknown key {:?}", key))),
+            }
+        }
+
+        Ok(spec)
+    }
+
+    /// Writes the spec in the form `load` reads, leaving out anything that's the default.
+    pub fn to_toml(&self) -> String {
+        let mut document = DocumentMut::new();
+
+        if self.lib {
+            document.insert("lib", toml_edit::value(true));
+        }
+
+        if !self.package.is_empty() {
+            let mut package = Table::new();
+
+            for (key, value) in &self.package {
+                package.insert(key, toml_edit::value(value.clone()));
+            }
+
+            document.insert("package", Item::Table(package));
+        }
+
+        if !self.profiles.is_empty() {
+            let profiles: Array = self.profiles.iter().map(String::as_str).collect();
+
+            let mut dependencies = Table::new();
+            dependencies.insert("profiles", toml_edit::value(profiles));
+
+            document.insert("dependencies", Item::Table(dependencies));
+        }
+
+        if !self.bins.is_empty() {
+            let mut bins = ArrayOfTables::new();
+
+            for bin in &self.bins {
+                let mut table = Table::new();
+                table.insert("name", toml_edit::value(bin.name.as_str()));
+
+                if let Some(template) = &bin.template {
+                    table.insert("template", toml_edit::value(template.as_str()));
+                }
+
+                if bin.layout == Layout::Dir {
+                    table.insert("layout", toml_edit::value("dir"));
+                }
+
+                bins.push(table);
+            }
+
+            document.insert("bin", Item::ArrayOfTables(bins));
+        }
+
+        if !self.files.is_empty() {
+            let mut files = ArrayOfTables::new();
+
+            for file in &self.files {
+                let mut table = Table::new();
+                table.insert("path", toml_edit::value(file.path.as_str()));
+                table.insert("contents", toml_edit::value(file.contents.as_str()));
+
+                files.push(table);
+            }
+
+            document.insert("file", Item::ArrayOfTables(files));
+        }
+
+        document.to_string()
+    }
+}
+
+/// The tables of an array of tables such as `[[bin]]`.
+fn tables<'a>(item: &'a Item, name: &str) -> Result<Vec<&'a Table>, String> {
+    item.as_array_of_tables()
+        .map(|tables| tables.iter().collect())
+        .ok_or_else(|| format!("{} must be written as [[{}]] tables", name, name))
+}
+
+fn check_keys(table: &Table, name: &str, known: &[&str]) -> Result<(), String> {
+    match table.iter().find(|(key, _)| !known.contains(key)) {
+        Some((key, _)) => Err(format!("Unknown key {:?} in [{}]", key, name)),
+        None => Ok(()),
+    }
+}
+
+fn string<'a>(table: &'a Table, name: &str, key: &str) -> Result<Option<&'a str>, String> {
+    match table.get(key) {
+        Some(item) => item
+            .as_str()
+            .map(Some)
+            .ok_or_else(|| format!("{}.{} must be a string", name, key)),
+        None => Ok(None),
+    }
+}
+
+fn string_list(item: Option<&Item>) -> Option<Vec<String>> {
+    match item {
+        Some(item) => item
+            .as_array()?
+            .iter()
+            .map(|value| value.as_str().map(|value| value.to_string()))
+            .collect(),
+        None => Some(vec![]),
+    }
+}
+
+fn bin_spec(table: &Table) -> Result<BinSpec, String> {
+    check_keys(table, "[bin]", &["name", "template", "layout"])?;
+
+    let name = string(table, "bin", "name")?.ok_or("Every [[bin]] needs a name")?;
+    let name = names::script_name(name).map_err(|e| e.to_string())?;
+
+    let layout = match string(table, "bin", "layout")? {
+        Some(layout) => layout.parse()?,
+        None => Layout::File,
+    };
+
+    Ok(BinSpec {
+        name,
+        template: string(table, "bin", "template")?.map(|template| template.to_string()),
+        layout,
+    })
+}
+
+fn file_spec(table: &Table) -> Result<FileSpec, String> {
+    check_keys(table, "[file]", &["path", "contents"])?;
+
+    let path = string(table, "file", "path")?.ok_or("Every [[file]] needs a path")?;
+
+    // Keep generated files inside the package.
+    let inside = PathBuf::from(path)
+        .components()
+        .all(|component| matches!(component, Component::Normal(_) | Component::CurDir));
+
+    if !inside {
+        return Err(format!(
+            "{:?} must be a path inside the package, relative to its root",
+            path
+        ));
+    }
+
+    Ok(FileSpec {
+        path: path.to_string(),
+        contents: string(table, "file", "contents")?
+            .ok_or_else(|| format!("The [[file]] for {} needs contents", path))?
+            .to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(contents: &str) -> Result<Spec, String> {
+        Spec::parse(Path::new(SPEC_FILE), contents).map_err(|e| e.to_string())
+    }
+
+    const FULL_SPEC: &str = r#"lib = true
+
+[package]
+description = "Does the thing"
+license = "MIT"
+
+[dependencies]
+profiles = ["cli"]
+
+[[bin]]
+name = "my-tool"
+template = "harness"
+layout = "dir"
+
+[[bin]]
+name = "other"
+
+[[file]]
+path = "config/default.toml"
+contents = "level = \"info\"\n"
+"#;
+
+    #[test]
+    fn reads_every_section() {
+        let spec = parse(FULL_SPEC).unwrap();
+
+        assert!(spec.lib);
+        assert_eq!(spec.package.len(), 2);
+        assert_eq!(spec.package[0].0, "description");
+        assert_eq!(spec.package[0].1.as_str(), Some("Does the thing"));
+        assert_eq!(spec.profiles, ["cli"]);
+        assert_eq!(spec.bins.len(), 2);
+        assert_eq!(spec.bins[0].template.as_deref(), Some("harness"));
+        assert!(spec.bins[0].layout == Layout::Dir);
+        assert!(spec.bins[1].layout == Layout::File);
+        assert_eq!(spec.files[0].path, "config/default.toml");
+        assert_eq!(spec.files[0].contents, "level = \"info\"\n");
+    }
+
+    #[test]
+    fn empty_spec_wants_nothing() {
+        let spec = parse("").unwrap();
+
+        assert!(!spec.lib && spec.package.is_empty() && spec.bins.is_empty());
+        assert!(spec.profiles.is_empty() && spec.files.is_empty());
+    }
+
+    #[test]
+    fn refuses_mistakes() {
+        for contents in &[
+            "lib = \"yes\"",
+            "unknown = 1",
+            "[dependencies]\nprofile = [\"cli\"]",
+            "[dependencies]\nprofiles = \"cli\"",
+            "[bin]\nname = \"tool\"",
+            "[[bin]]\ntemplate = \"harness\"",
+            "[[bin]]\nname = \"fn\"",
+            "[[bin]]\nname = \"tool\"\nlayout = \"tree\"",
+            "[[bin]]\nname = \"tool\"\n\n[[bin]]\nname = \"tool\"",
+            "[[file]]\npath = \"../outside\"\ncontents = \"\"",
+            "[[file]]\npath = \"/etc/passwd\"\ncontents = \"\"",
+            "[[file]]\npath = \"config.toml\"",
+        ] {
+            assert!(parse(contents).is_err(), "{:?} was accepted", contents);
+        }
+    }
+}
@@ -0,0 +1,493 @@
+use crate::error::GrumpyError;
+use crate::manifest::Manifest;
+use crate::transaction::Transaction;
+use crate::CargoCommand;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// A library or binary target of the package.
+pub struct Target {
+    pub name: String,
+    pub kinds: Vec<String>,
+    pub src_path: PathBuf,
+}
+
+/// How a new binary's source is laid out, chosen with `--layout`.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Layout {
+    /// A single `src/bin/<name>.rs`.
+    File,
+    /// A `src/bin/<name>/main.rs`, with sibling modules alongside it.
+    Dir,
+}
+
+impl FromStr for Layout {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "file" => Ok(Layout::File),
+            "dir" => Ok(Layout::Dir),
+            _ => Err(format!("unknown layout {:?}, expected file or dir", s)),
+        }
+    }
+}
+
+impl Layout {
+    /// The file cargo builds the binary from, relative to the binaries directory.
+    pub fn source_path(self, bin_name: &str) -> PathBuf {
+        match self {
+            Layout::File => PathBuf::from(format!("{}.rs", bin_name)),
+            Layout::Dir => Path::new(bin_name).join("main.rs"),
+        }
+    }
+}
+
+/// Where a new binary's source should go.
+pub enum Placement {
+    /// The package's only binary is `src/main.rs`, so a new script would take its place.
+    Main(PathBuf),
+    /// Alongside other binaries, in this directory. When `declare` is set cargo won't find the
+    /// file by itself, so it also needs a `[[bin]]` table in Cargo.toml.
+    BinDir { dir: PathBuf, declare: bool },
+}
+
+/// The package's targets, as cargo sees them.
+pub struct Targets {
+    manifest_path: PathBuf,
+    lib: Option<Target>,
+    bins: Vec<Target>,
+    /// Examples, tests and benches.
+    others: Vec<Target>,
+    autobins: bool,
+}
+
+impl Targets {
+    /// Asks `cargo metadata` for the package's targets, so that custom paths and explicit
+    /// `[[bin]]` tables are understood exactly as cargo understands them.
+    ///
+    /// When a dry run has already planned changes to the package, such as a `new` that has
+    /// nothing on disk yet, cargo would only see the old layout. The targets are worked out from
+    /// the planned manifest and files instead, as cargo would: the `[lib]` and `[[bin]]` tables
+    /// first, then `src/lib.rs`, `src/main.rs` and the binaries in `src/bin` unless the package
+    /// turns that off.
+    pub fn load(transaction: &Transaction, project_root: &Path) -> Result<Self, GrumpyError> {
+        let manifest_path = project_root.join("Cargo.toml");
+        let manifest = Manifest::load(transaction, project_root)?;
+
+        let mut targets = if !transaction.has_planned_changes(project_root) {
+            Targets::from_metadata(&manifest_path)?
+        } else {
+            Targets::from_files(transaction, project_root, &manifest)
+        };
+
+        targets.autobins = manifest.autobins();
+
+        Ok(targets)
+    }
+
+    fn from_metadata(manifest_path: &Path) -> Result<Self, GrumpyError> {
+        let mut command = CargoCommand::new("metadata");
+
+        command
+            .add_arg("--offline")
+            .add_arg("--no-deps")
+            .add_arg("--format-version")
+            .add_arg("1")
+            .add_arg("--manifest-path")
+            .add_arg(&manifest_path.to_string_lossy());
+
+        let metadata: serde_json::Value =
+            serde_json::from_str(&command.capture()?).map_err(|e| {
+                unsupported(
+                    manifest_path,
+                    format!("cargo metadata printed something unexpected: {}", e),
+                )
+            })?;
+
+        // In a workspace every member is listed, so pick out this package by its manifest.
+        let wanted = canonical(manifest_path);
+
+        let package = metadata["packages"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .find(|package| {
+                package["manifest_path"]
+                    .as_str()
+                    .is_some_and(|path| canonical(Path::new(path)) == wanted)
+            })
+            .ok_or_else(|| {
+                unsupported(
+                    manifest_path,
+                    "cargo metadata doesn't list this package".to_string(),
+                )
+            })?;
+
+        let mut targets = Targets {
+            manifest_path: manifest_path.to_path_buf(),
+            lib: None,
+            bins: vec![],
+            others: vec![],
+            autobins: true,
+        };
+
+        for target in package["targets"].as_array().into_iter().flatten() {
+            let target = Target {
+                name: target["name"].as_str().unwrap_or_default().to_string(),
+                kinds: target["kind"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|kind| kind.as_str())
+                    .map(|kind| kind.to_string())
+                    .collect(),
+                src_path: PathBuf::from(target["src_path"].as_str().unwrap_or_default()),
+            };
+
+            if target.kinds.iter().any(|kind| kind == "bin") {
+                targets.bins.push(target);
+            } else if target
+                .kinds
+                .iter()
+                .any(|kind| matches!(kind.as_str(), "example" | "test" | "bench"))
+            {
+                targets.others.push(target);
+            } else if !target.kinds.iter().any(|kind| kind == "custom-build") {
+                targets.lib = Some(target);
+            }
+        }
+
+        Ok(targets)
+    }
+
+    fn from_files(transaction: &Transaction, project_root: &Path, manifest: &Manifest) -> Self {
+        let source_root = project_root.join("src");
+        let package_name = manifest.package_name().unwrap_or_default();
+
+        let declared_lib = manifest.lib_target();
+
+        let lib_path = match declared_lib.as_ref().and_then(|lib| lib.path.as_ref()) {
+            Some(path) => Some(project_root.join(path)),
+            None => Some(source_root.join("lib.rs")).filter(|path| {
+                (declared_lib.is_some() || manifest.autolib()) && transaction.exists(path)
+            }),
+        };
+
+        let lib = lib_path.map(|src_path| {
+            let (name, crate_types) = match declared_lib {
+                Some(lib) => (lib.name, lib.crate_types),
+                None => (None, vec![]),
+            };
+
+            Target {
+                name: name.unwrap_or_else(|| package_name.replace('-', "_")),
+                kinds: if crate_types.is_empty() {
+                    vec!["lib".to_string()]
+                } else {
+                    crate_types
+                },
+                src_path,
+            }
+        });
+
+        // Declared binaries come first, with any path they don't give found as cargo would.
+        let mut bins: Vec<Target> = manifest
+            .bin_targets()
+            .into_iter()
+            .filter_map(|bin| {
+                let name = bin.name?;
+                let src_path = match bin.path {
+                    Some(path) => project_root.join(path),
+                    None => default_bin_path(transaction, &source_root, package_name, &name),
+                };
+
+                Some(Target {
+                    name,
+                    kinds: vec!["bin".to_string()],
+                    src_path,
+                })
+            })
+            .collect();
+
+        if manifest.autobins() {
+            let main = Some(source_root.join("main.rs"))
+                .filter(|path| transaction.exists(path))
+                .map(|src_path| Target {
+                    name: package_name.to_string(),
+                    kinds: vec!["bin".to_string()],
+                    src_path,
+                });
+
+            let found: Vec<Target> = main
+                .into_iter()
+                .chain(
+                    transaction
+                        .entries(&source_root.join("bin"))
+                        .into_iter()
+                        .filter_map(|path| bin_dir_target(transaction, path)),
+                )
+                .filter(|found| {
+                    !bins
+                        .iter()
+                        .any(|bin| bin.name == found.name || bin.src_path == found.src_path)
+                })
+                .collect();
+
+            bins.extend(found);
+        }
+
+        Targets {
+            manifest_path: project_root.join("Cargo.toml"),
+            lib,
+            bins,
+            others: vec![],
+            autobins: true,
+        }
+    }
+
+    pub fn has_lib(&self) -> bool {
+        self.lib.is_some()
+    }
+
+    pub fn lib(&self) -> Option<&Target> {
+        self.lib.as_ref()
+    }
+
+    pub fn bins(&self) -> &[Target] {
+        &self.bins
+    }
+
+    pub fn bin(&self, name: &str) -> Option<&Target> {
+        self.bins.iter().find(|bin| bin.name == name)
+    }
+
+    /// Every target, with the library first, then binaries, then examples, tests and benches.
+    pub fn all(&self) -> impl Iterator<Item = &Target> {
+        self.lib.iter().chain(&self.bins).chain(&self.others)
+    }
+
+    /// Decides where a new binary goes, refusing layouts a new binary can't sensibly join.
+    pub fn placement(&self, project_root: &Path) -> Result<Placement, GrumpyError> {
+        if let Some(lib) = &self.lib {
+            if lib.kinds.iter().any(|kind| kind == "proc-macro") {
+                return Err(unsupported(
+                    &self.manifest_path,
+                    "binaries can't use a proc-macro library".to_string(),
+                ));
+            }
+
+            if !lib.kinds.iter().any(|kind| kind == "lib" || kind == "rlib") {
+                return Err(unsupported(
+                    &self.manifest_path,
+                    format!(
+                        "the library is only built as {}, which binaries can't use",
+                        lib.kinds.join(", ")
+                    ),
+                ));
+            }
+        }
+
+        let main_path = project_root.join("src").join("main.rs");
+
+        match (&self.lib, self.bins.as_slice()) {
+            (None, []) => Ok(Placement::Main(main_path)),
+            (None, [only]) if canonical(&only.src_path) == canonical(&main_path) => {
+                Ok(Placement::Main(main_path))
+            }
+            _ => Ok(Placement::BinDir {
+                dir: project_root.join("src").join("bin"),
+                declare: !self.autobins,
+            }),
+        }
+    }
+}
+
+/// The binary cargo would find at a path in `src/bin`, either `<name>.rs` or `<name>/main.rs`.
+fn bin_dir_target(transaction: &Transaction, path: PathBuf) -> Option<Target> {
+    let src_path = if transaction.is_dir(&path) {
+        path.join("main.rs")
+    } else if path.extension().is_some_and(|extension| extension == "rs") {
+        path.clone()
+    } else {
+        return None;
+    };
+
+    if !transaction.exists(&src_path) {
+        return None;
+    }
+
+    let name = path.file_stem()?.to_string_lossy().to_string();
+
+    Some(Target {
+        name,
+        kinds: vec!["bin".to_string()],
+        src_path,
+    })
+}
+
+/// Where cargo looks for a declared binary that doesn't give a path: `src/bin/<name>.rs`, then
+/// `src/bin/<name>/main.rs`, then `src/main.rs` for a binary named after the package.
+fn default_bin_path(
+    transaction: &Transaction,
+    source_root: &Path,
+    package_name: &str,
+    name: &str,
+) -> PathBuf {
+    let bin_dir = source_root.join("bin");
+    let mut candidates = vec![
+        bin_dir.join(Layout::File.source_path(name)),
+        bin_dir.join(Layout::Dir.source_path(name)),
+    ];
+
+    if name == package_name {
+        candidates.push(source_root.join("main.rs"));
+    }
+
+    candidates
+        .iter()
+        .find(|path| transaction.exists(path))
+        .unwrap_or(&candidates[0])
+        .clone()
+}
+
+fn unsupported(manifest_path: &Path, message: String) -> GrumpyError {
+    GrumpyError::UnsupportedLayout {
+        path: manifest_path.to_path_buf(),
+        message,
+    }
+}
+
+/// Gives a path relative to the project root, as Cargo.toml refers to it. Paths from cargo have
+/// their symlinks resolved, so the root is tried both ways.
+pub fn manifest_relative(project_root: &Path, path: &Path) -> String {
+    path.strip_prefix(project_root)
+        .or_else(|_| path.strip_prefix(canonical(project_root)))
+        .unwrap_or(path)
+        .to_string_lossy()
+        .to_string()
+}
+
+/// Resolves symlinks so that paths from cargo compare equal to ours, falling back to the path as
+/// given for anything that doesn't exist yet.
+fn canonical(path: &Path) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A dry run with a package planned at a path that doesn't exist, so nothing is on disk.
+    fn planned_package(files: &[(&str, &str)]) -> (Transaction, PathBuf) {
+        let mut transaction = Transaction::new(true);
+        let root = PathBuf::from("/nonexistent/cargo-grumpy-test");
+
+        for (path, contents) in files {
+            transaction
+                .write(&root.join(path), contents.as_bytes())
+                .unwrap();
+        }
+
+        (transaction, root)
+    }
+
+    fn bin_names(targets: &Targets) -> Vec<&str> {
+        targets.bins().iter().map(|bin| bin.name.as_str()).collect()
+    }
+
+    #[test]
+    fn planned_files_include_binaries_under_src_bin() {
+        let (transaction, root) = planned_package(&[
+            ("Cargo.toml", "[package]\nname = \"demo\"\n"),
+            ("src/lib.rs", ""),
+            ("src/bin/one.rs", ""),
+            ("src/bin/two/main.rs", ""),
+            ("src/bin/two/cli.rs", ""),
+            ("src/bin/notes.txt", ""),
+        ]);
+
+        let targets = Targets::load(&transaction, &root).unwrap();
+
+        assert!(targets.has_lib());
+        assert_eq!(bin_names(&targets), ["one", "two"]);
+        assert_eq!(
+            targets.bin("two").unwrap().src_path,
+            root.join("src/bin/two/main.rs")
+        );
+    }
+
+    #[test]
+    fn planned_files_skip_src_bin_without_autobins() {
+        let (transaction, root) = planned_package(&[
+            (
+                "Cargo.toml",
+                "[package]\nname = \"demo\"\nautobins = false\n\n[[bin]]\nname = \"tool\"\npath = \"src/tool.rs\"\n",
+            ),
+            ("src/main.rs", ""),
+            ("src/bin/one.rs", ""),
+            ("src/tool.rs", ""),
+        ]);
+
+        let targets = Targets::load(&transaction, &root).unwrap();
+
+        assert_eq!(bin_names(&targets), ["tool"]);
+    }
+
+    #[test]
+    fn planned_main_binary_keeps_the_package_name() {
+        let (transaction, root) = planned_package(&[
+            ("Cargo.toml", "[package]\nname = \"my-tool\"\n"),
+            ("src/lib.rs", ""),
+            ("src/main.rs", ""),
+        ]);
+
+        let targets = Targets::load(&transaction, &root).unwrap();
+
+        assert_eq!(targets.lib().unwrap().name, "my_tool");
+        assert_eq!(bin_names(&targets), ["my-tool"]);
+    }
+
+    #[test]
+    fn planned_files_follow_declared_targets() {
+        let (transaction, root) = planned_package(&[
+            (
+                "Cargo.toml",
+                "[package]\nname = \"demo\"\nedition = \"2021\"\n\n[lib]\npath = \"lib/demo.rs\"\n\n[[bin]]\nname = \"custom\"\npath = \"tools/custom.rs\"\n\n[[bin]]\nname = \"one\"\n",
+            ),
+            ("lib/demo.rs", ""),
+            ("tools/custom.rs", ""),
+            ("src/bin/one/main.rs", ""),
+            ("src/bin/two.rs", ""),
+        ]);
+
+        let targets = Targets::load(&transaction, &root).unwrap();
+
+        assert_eq!(targets.lib().unwrap().src_path, root.join("lib/demo.rs"));
+        assert_eq!(bin_names(&targets), ["custom", "one", "two"]);
+        assert_eq!(
+            targets.bin("custom").unwrap().src_path,
+            root.join("tools/custom.rs")
+        );
+        assert_eq!(
+            targets.bin("one").unwrap().src_path,
+            root.join("src/bin/one/main.rs")
+        );
+    }
+
+    #[test]
+    fn planned_proc_macro_libraries_are_refused() {
+        let (transaction, root) = planned_package(&[
+            (
+                "Cargo.toml",
+                "[package]\nname = \"demo\"\n\n[lib]\nproc-macro = true\n",
+            ),
+            ("src/lib.rs", ""),
+        ]);
+
+        let targets = Targets::load(&transaction, &root).unwrap();
+
+        assert!(targets.placement(&root).is_err());
+    }
+}
@@ -0,0 +1,459 @@
+use crate::dirs;
+use crate::error::{io_error, GrumpyError};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Name of the template used for executable scripts when none is specified.
+pub const DEFAULT_TEMPLATE: &str = "harness";
+
+/// Text that marks a file as written by cargo-grumpy, which it may then keep up to date, such as
+/// when a binary is renamed.
+pub const GENERATED_MARKER: &str = "generated by cargo-grumpy";
+
+/// Where a rendered file came from, recorded as its first line:
+///
+/// ```text
+/// // generated by cargo-grumpy from template harness version 5f2c7e1a09b3d4c8 checksum 0e4f...
+/// ```
+///
+/// The version is a checksum of the template's source, so it changes whenever the template does.
+/// The checksum covers everything after the header as it was rendered, so any later edit shows.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Header {
+    pub template: String,
+    pub version: String,
+    pub checksum: String,
+}
+
+impl Header {
+    /// Reads the header from a file's first line, returning it along with the rest of the file.
+    pub fn parse(contents: &str) -> Option<(Header, &str)> {
+        let (first_line, body) = match contents.find('\n') {
+            Some(end) => (&contents[..end], &contents[end + 1..]),
+            None => (contents, ""),
+        };
+
+        let fields = first_line
+            .strip_prefix("// ")?
+            .strip_prefix(GENERATED_MARKER)?
+            .split_whitespace()
+            .collect::<Vec<_>>();
+
+        match fields.as_slice() {
+            ["from", "template", template, "version", version, "checksum", checksum] => Some((
+                Header {
+                    template: template.to_string(),
+                    version: version.to_string(),
+                    checksum: checksum.to_string(),
+                },
+                body,
+            )),
+            _ => None,
+        }
+    }
+
+    /// Whether the file has been changed since it was rendered.
+    pub fn is_modified(&self, body: &str) -> bool {
+        checksum(body) != self.checksum
+    }
+}
+
+/// Puts a header recording the template and what was rendered in front of a rendered file.
+fn with_header(template: &str, source: &str, rendered: &str) -> String {
+    format!(
+        "// {} from template {} version {} checksum {}\n{}",
+        GENERATED_MARKER,
+        template,
+        checksum(source),
+        checksum(rendered),
+        rendered
+    )
+}
+
+/// A 64-bit FNV-1a hash as hex, which is plenty to notice a change without pulling in a hashing
+/// crate.
+pub fn checksum(text: &str) -> String {
+    let hash = text.bytes().fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0000_0100_0000_01b3)
+    });
+
+    format!("{:016x}", hash)
+}
+
+/// Templates compiled into the binary, used when no template directory overrides them.
+const BUILTIN_TEMPLATES: &[(&str, &str)] =
+    &[("harness", include_str!("../templates/harness.rs.tmpl"))];
+
+/// Directory templates compiled into the binary, each a set of files for the
+/// `src/bin/<name>/main.rs` layout.
+const BUILTIN_DIR_TEMPLATES: &[(&str, &[(&str, &str)])] = &[(
+    "harness",
+    &[
+        ("main.rs", include_str!("../templates/harness/main.rs.tmpl")),
+        ("cli.rs", include_str!("../templates/harness/cli.rs.tmpl")),
+        (
+            "config.rs",
+            include_str!("../templates/harness/config.rs.tmpl"),
+        ),
+    ],
+)];
+
+/// README and CI files written alongside a new project's binary, by path relative to the project
+/// root. Each carries [`GENERATED_MARKER`], so later commands know they may keep it up to date.
+const PROJECT_FILES: &[(&str, &str)] = &[
+    (
+        "README.md",
+        include_str!("../templates/project/README.md.tmpl"),
+    ),
+    (
+        ".github/workflows/ci.yml",
+        include_str!("../templates/project/ci.yml.tmpl"),
+    ),
+];
+
+/// Renders the README and CI files for a new project, returning each path along with its
+/// contents.
+pub fn render_project_files(
+    context: &TemplateContext,
+) -> Result<Vec<(&'static str, String)>, GrumpyError> {
+    PROJECT_FILES
+        .iter()
+        .map(|(path, source)| {
+            render_source(source, context)
+                .map(|contents| (*path, contents))
+                .map_err(|variable| GrumpyError::UnknownTemplateVariable {
+                    template: path.to_string(),
+                    variable,
+                })
+        })
+        .collect()
+}
+
+/// Variables available for substitution when rendering a template.
+///
+/// Templates refer to variables as `{{ name }}`. Anything else between braces is left alone, so
+/// Rust format strings survive rendering untouched.
+pub struct TemplateContext {
+    variables: BTreeMap<String, String>,
+}
+
+impl TemplateContext {
+    pub fn new() -> Self {
+        TemplateContext {
+            variables: BTreeMap::new(),
+        }
+    }
+
+    pub fn set(&mut self, name: &str, value: &str) -> &mut Self {
+        self.variables.insert(name.to_string(), value.to_string());
+
+        self
+    }
+}
+
+/// Looks up templates by name and renders them.
+///
+/// Each search path is checked in order for a file called `<name>.rs.tmpl`, or a directory called
+/// `<name>` for directory templates, before falling back to the templates built into
+/// cargo-grumpy.
+pub struct TemplateEngine {
+    search_paths: Vec<PathBuf>,
+}
+
+impl TemplateEngine {
+    /// Creates an engine that searches the project's `.grumpy/templates` directory, then the team
+    /// template directory if there is one, then the user's own templates directory.
+    pub fn new(project_root: &Path, team_dir: Option<&Path>) -> Self {
+        let mut search_paths = vec![project_root.join(".grumpy").join("templates")];
+
+        if let Some(team_dir) = team_dir.filter(|dir| !dir.as_os_str().is_empty()) {
+            search_paths.push(team_dir.to_path_buf());
+        }
+
+        if let Some(user_dir) = dirs::user_config_dir() {
+            search_paths.push(user_dir.join("templates"));
+        }
+
+        TemplateEngine { search_paths }
+    }
+
+    /// Renders a template, starting the result with a [`Header`].
+    pub fn render(&self, name: &str, context: &TemplateContext) -> Result<String, GrumpyError> {
+        let source = self.load(name)?;
+
+        let rendered = render_source(&source, context).map_err(|variable| {
+            GrumpyError::UnknownTemplateVariable {
+                template: name.to_string(),
+                variable,
+            }
+        })?;
+
+        Ok(with_header(name, &source, &rendered))
+    }
+
+    /// Renders every `<file>.rs.tmpl` in a directory template, returning each file's name without
+    /// the `.tmpl` along with its contents. A directory template must have a `main.rs.tmpl`.
+    /// Each file gets a [`Header`] naming it as `<name>/<file>`.
+    pub fn render_dir(
+        &self,
+        name: &str,
+        context: &TemplateContext,
+    ) -> Result<Vec<(String, String)>, GrumpyError> {
+        let mut rendered = vec![];
+
+        for (file_name, source) in self.load_dir(name)? {
+            let template = format!("{}/{}", name, file_name);
+
+            let contents = render_source(&source, context).map_err(|variable| {
+                GrumpyError::UnknownTemplateVariable {
+                    template: template.clone(),
+                    variable,
+                }
+            })?;
+
+            rendered.push((file_name, with_header(&template, &source, &contents)));
+        }
+
+        Ok(rendered)
+    }
+
+    /// The current version of a template named as in a [`Header`], or `None` if it can no longer
+    /// be found.
+    pub fn version(&self, template: &str) -> Option<String> {
+        let source = match template.split_once('/') {
+            Some((name, file_name)) => self
+                .load_dir(name)
+                .ok()?
+                .into_iter()
+                .find(|(found, _)| found == file_name)
+                .map(|(_, source)| source)?,
+            None => self.load(template).ok()?,
+        };
+
+        Some(checksum(&source))
+    }
+
+    fn load_dir(&self, name: &str) -> Result<Vec<(String, String)>, GrumpyError> {
+        let mut files = None;
+
+        for search_path in &self.search_paths {
+            let template_dir = search_path.join(name);
+
+            if template_dir.is_dir() {
+                let mut found = vec![];
+
+                for entry in
+                    fs::read_dir(&template_dir).map_err(io_error("read template", &template_dir))?
+                {
+                    let path = entry
+                        .map_err(io_error("read template", &template_dir))?
+                        .path();
+
+                    let file_name = path.file_name().and_then(|file_name| file_name.to_str());
+
+                    if let Some(file_name) = file_name.and_then(|f| f.strip_suffix(".tmpl")) {
+                        if file_name.ends_with(".rs") {
+                            let source = fs::read_to_string(&path)
+                                .map_err(io_error("read template", &path))?;
+
+                            found.push((file_name.to_string(), source));
+                        }
+                    }
+                }
+
+                found.sort();
+                files = Some(found);
+                break;
+            }
+        }
+
+        let files = match files {
+            Some(files) => files,
+            None => BUILTIN_DIR_TEMPLATES
+                .iter()
+                .find(|(builtin_name, _)| *builtin_name == name)
+                .map(|(_, files)| {
+                    files
+                        .iter()
+                        .map(|(file_name, source)| (file_name.to_string(), source.to_string()))
+                        .collect()
+                })
+                .unwrap_or_default(),
+        };
+
+        if !files.iter().any(|(file_name, _)| file_name == "main.rs") {
+            return Err(GrumpyError::TemplateNotFound {
+                name: format!("{}/main.rs", name),
+            });
+        }
+
+        Ok(files)
+    }
+
+    fn load(&self, name: &str) -> Result<String, GrumpyError> {
+        for search_path in &self.search_paths {
+            let template_path = search_path.join(format!("{}.rs.tmpl", name));
+
+            if template_path.exists() {
+                return fs::read_to_string(&template_path)
+                    .map_err(io_error("read template", &template_path));
+            }
+        }
+
+        BUILTIN_TEMPLATES
+            .iter()
+            .find(|(builtin_name, _)| *builtin_name == name)
+            .map(|(_, source)| source.to_string())
+            .ok_or_else(|| GrumpyError::TemplateNotFound {
+                name: name.to_string(),
+            })
+    }
+}
+
+/// Substitutes every `{{ variable }}` in `source`, returning the name of the first variable that
+/// isn't defined in the context.
+fn render_source(source: &str, context: &TemplateContext) -> Result<String, String> {
+    let mut rendered = String::with_capacity(source.len());
+    let mut remaining = source;
+
+    while let Some(start) = remaining.find("{{") {
+        rendered.push_str(&remaining[..start]);
+
+        let after_open = &remaining[start + 2..];
+
+        let variable = after_open.find("}}").and_then(|end| {
+            let name = after_open[..end].trim();
+
+            if !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+                Some((name, end))
+            } else {
+                None
+            }
+        });
+
+        match variable {
+            Some((name, end)) => {
+                match context.variables.get(name) {
+                    Some(value) => rendered.push_str(value),
+                    None => return Err(name.to_string()),
+                }
+
+                remaining = &after_open[end + 2..];
+            }
+            None => {
+                // Not a substitution, so keep the braces and carry on after them.
+                rendered.push_str("{{");
+                remaining = after_open;
+            }
+        }
+    }
+
+    rendered.push_str(remaining);
+
+    Ok(rendered)
+}
+
+/// Returns the current year in UTC, for copyright lines and the like.
+pub fn current_year() -> i64 {
+    let days = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs() / 86_400)
+        .unwrap_or(0) as i64;
+
+    // Civil-from-days conversion, see http://howardhinnant.github.io/date_algorithms.html
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let year = year_of_era + era * 400;
+
+    if month_index >= 10 {
+        year + 1
+    } else {
+        year
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn context() -> TemplateContext {
+        let mut context = TemplateContext::new();
+        context.set("name", "grumpy");
+        context
+    }
+
+    #[test]
+    fn substitutes_variables() {
+        assert_eq!(
+            render_source("Hello {{ name }}, {{name}}!", &context()),
+            Ok("Hello grumpy, grumpy!".to_string())
+        );
+    }
+
+    #[test]
+    fn leaves_other_braces_alone() {
+        assert_eq!(
+            render_source("println!(\"{{}} {}\", x); {{ not a name }} {{", &context()),
+            Ok("println!(\"{{}} {}\", x); {{ not a name }} {{".to_string())
+        );
+    }
+
+    #[test]
+    fn reports_unknown_variables() {
+        assert_eq!(
+            render_source("{{ name }} {{ missing }}", &context()),
+            Err("missing".to_string())
+        );
+    }
+
+    #[test]
+    fn header_round_trips() {
+        let contents = with_header("harness", "source", "fn main() {}\n");
+        let (header, body) = Header::parse(&contents).unwrap();
+
+        assert_eq!(header.template, "harness");
+        assert_eq!(header.version, checksum("source"));
+        assert_eq!(body, "fn main() {}\n");
+        assert!(!header.is_modified(body));
+        assert!(header.is_modified("fn main() { todo!() }\n"));
+    }
+
+    #[test]
+    fn files_without_a_header_have_none() {
+        assert_eq!(Header::parse("fn main() {}\n"), None);
+        assert_eq!(
+            Header::parse("// generated by cargo-grumpy from somewhere else\n"),
+            None
+        );
+    }
+
+    #[test]
+    fn checksums_are_stable() {
+        assert_eq!(checksum(""), "cbf29ce484222325");
+        assert_ne!(checksum("a"), checksum("b"));
+    }
+
+    #[test]
+    fn project_files_are_marked_and_name_the_binary() {
+        let mut context = TemplateContext::new();
+        context
+            .set("project_name", "grumpy")
+            .set("bin_name", "tool");
+
+        for (path, contents) in render_project_files(&context).unwrap() {
+            assert!(
+                contents.contains(GENERATED_MARKER),
+                "{} has no marker",
+                path
+            );
+            assert!(contents.contains("--bin tool"), "{} has no binary", path);
+        }
+    }
+}
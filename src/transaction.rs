@@ -0,0 +1,397 @@
+use crate::diff;
+use crate::output::{self, Event, MessageFormat};
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// A single change made to the filesystem, recorded so it can be undone.
+enum Change {
+    CreatedFile(PathBuf),
+    CreatedDir(PathBuf),
+    /// A whole directory tree made by something outside our control, such as `cargo new`.
+    CreatedTree(PathBuf),
+    /// A file that was overwritten or deleted, along with what it held beforehand.
+    ReplacedFile(PathBuf, Vec<u8>),
+    /// An empty directory that was deleted, once everything in it had been.
+    RemovedDir(PathBuf),
+}
+
+/// Records every file and directory change made while scaffolding, so that a failure part way
+/// through puts the project back the way it was.
+///
+/// Changes are applied straight away, and undone in reverse order if the transaction is rolled
+/// back or dropped without being committed. Dropping covers panics too, as the transaction is
+/// unwound along with everything else.
+///
+/// A dry run transaction never touches the disk. Changes are held in memory instead, and reads
+/// through the transaction see them, so later steps behave as though earlier ones happened.
+/// Committing a dry run prints what would have been done.
+pub struct Transaction {
+    journal: Vec<Change>,
+    committed: bool,
+    dry_run: bool,
+    /// Planned file contents for a dry run, with `None` for files that would be deleted.
+    planned_files: BTreeMap<PathBuf, Option<Vec<u8>>>,
+    planned_dirs: Vec<PathBuf>,
+    planned_commands: Vec<String>,
+}
+
+impl Transaction {
+    pub fn new(dry_run: bool) -> Self {
+        Transaction {
+            journal: vec![],
+            committed: false,
+            dry_run,
+            planned_files: BTreeMap::new(),
+            planned_dirs: vec![],
+            planned_commands: vec![],
+        }
+    }
+
+    pub fn is_dry_run(&self) -> bool {
+        self.dry_run
+    }
+
+    /// Notes a command that a dry run skipped, to be listed alongside the file changes.
+    pub fn plan_command(&mut self, command: &str) {
+        self.planned_commands.push(command.to_string());
+    }
+
+    pub fn exists(&self, path: &Path) -> bool {
+        match self.planned_files.get(path) {
+            Some(planned) => planned.is_some(),
+            None => self.planned_dirs.iter().any(|dir| dir == path) || path.exists(),
+        }
+    }
+
+    /// Whether anything has been changed so far, or for a dry run, planned.
+    pub fn has_changes(&self) -> bool {
+        !self.journal.is_empty()
+            || !self.planned_files.is_empty()
+            || !self.planned_dirs.is_empty()
+            || !self.planned_commands.is_empty()
+    }
+
+    pub fn is_dir(&self, path: &Path) -> bool {
+        self.planned_dirs.iter().any(|dir| dir == path) || path.is_dir()
+    }
+
+    /// Whether a dry run has planned any change inside `dir`, meaning what's on disk there is
+    /// out of date.
+    pub fn has_planned_changes(&self, dir: &Path) -> bool {
+        self.planned_files.keys().any(|path| path.starts_with(dir))
+            || self.planned_dirs.iter().any(|path| path.starts_with(dir))
+    }
+
+    /// What's directly inside a directory, as the transaction currently sees it, sorted by path.
+    pub fn entries(&self, dir: &Path) -> Vec<PathBuf> {
+        let mut entries: Vec<PathBuf> = fs::read_dir(dir)
+            .into_iter()
+            .flatten()
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .chain(self.planned_files.keys().cloned())
+            .chain(self.planned_dirs.iter().cloned())
+            .filter(|path| path.parent() == Some(dir) && self.exists(path))
+            .collect();
+
+        entries.sort();
+        entries.dedup();
+
+        entries
+    }
+
+    pub fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        match self.planned_files.get(path) {
+            Some(Some(contents)) => Ok(contents.clone()),
+            Some(None) => Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                "file would have been deleted",
+            )),
+            None => fs::read(path),
+        }
+    }
+
+    pub fn read_to_string(&self, path: &Path) -> io::Result<String> {
+        String::from_utf8(self.read(path)?)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    /// Notes a directory tree that was created outside the transaction, so that rolling back
+    /// removes it.
+    pub fn track_created_tree(&mut self, path: &Path) {
+        if !self.dry_run {
+            self.journal.push(Change::CreatedTree(path.to_path_buf()));
+        }
+    }
+
+    /// Notes a file that was written outside the transaction, along with what it held beforehand
+    /// if it was already there, so that rolling back puts it back.
+    pub fn track_written_file(&mut self, path: &Path, original: Option<Vec<u8>>) {
+        if !self.dry_run {
+            self.journal.push(match original {
+                Some(original) => Change::ReplacedFile(path.to_path_buf(), original),
+                None => Change::CreatedFile(path.to_path_buf()),
+            });
+        }
+    }
+
+    pub fn create_dir_all(&mut self, path: &Path) -> io::Result<()> {
+        if self.is_dir(path) {
+            return Ok(());
+        }
+
+        if let Some(parent) = path.parent() {
+            self.create_dir_all(parent)?;
+        }
+
+        if self.dry_run {
+            self.planned_dirs.push(path.to_path_buf());
+        } else {
+            fs::create_dir(path)?;
+            self.journal.push(Change::CreatedDir(path.to_path_buf()));
+        }
+
+        Ok(())
+    }
+
+    /// Writes a file, creating any missing parent directories.
+    pub fn write(&mut self, path: &Path, contents: &[u8]) -> io::Result<()> {
+        // Writing what's already there changes nothing, so there's nothing to report or undo.
+        if self.read(path).is_ok_and(|existing| existing == contents) {
+            return Ok(());
+        }
+
+        if let Some(parent) = path.parent() {
+            self.create_dir_all(parent)?;
+        }
+
+        if self.dry_run {
+            self.planned_files
+                .insert(path.to_path_buf(), Some(contents.to_vec()));
+        } else if path.exists() {
+            let original = fs::read(path)?;
+            fs::write(path, contents)?;
+            self.journal
+                .push(Change::ReplacedFile(path.to_path_buf(), original));
+        } else {
+            fs::write(path, contents)?;
+            self.journal.push(Change::CreatedFile(path.to_path_buf()));
+        }
+
+        Ok(())
+    }
+
+    pub fn remove_file(&mut self, path: &Path) -> io::Result<()> {
+        if self.dry_run {
+            // Reading first makes a missing file fail just as it would for real.
+            self.read(path)?;
+            self.planned_files.insert(path.to_path_buf(), None);
+        } else {
+            let original = fs::read(path)?;
+            fs::remove_file(path)?;
+            self.journal
+                .push(Change::ReplacedFile(path.to_path_buf(), original));
+        }
+
+        Ok(())
+    }
+
+    /// Deletes a directory and everything in it. Files are removed one at a time, so each can be
+    /// restored on roll back.
+    pub fn remove_dir_all(&mut self, path: &Path) -> io::Result<()> {
+        if path.is_dir() {
+            for entry in fs::read_dir(path)? {
+                let entry = entry?;
+
+                if entry.file_type()?.is_dir() {
+                    self.remove_dir_all(&entry.path())?;
+                } else {
+                    self.remove_file(&entry.path())?;
+                }
+            }
+        }
+
+        if self.dry_run {
+            let planned: Vec<PathBuf> = self
+                .planned_files
+                .iter()
+                .filter(|(file, contents)| file.starts_with(path) && contents.is_some())
+                .map(|(file, _)| file.clone())
+                .collect();
+
+            for file in planned {
+                self.planned_files.insert(file, None);
+            }
+
+            self.planned_dirs.retain(|dir| !dir.starts_with(path));
+        } else {
+            fs::remove_dir(path)?;
+            self.journal.push(Change::RemovedDir(path.to_path_buf()));
+        }
+
+        Ok(())
+    }
+
+    /// Keeps every change made so far, or for a dry run, prints them. With JSON messages, each
+    /// changed path is reported as an event instead.
+    pub fn commit(mut self) {
+        match output::format() {
+            MessageFormat::Human if self.dry_run => print!("{}", self.plan()),
+            MessageFormat::Human => {}
+            MessageFormat::Json => self.emit_changes(),
+        }
+
+        self.committed = true;
+    }
+
+    /// Reports the overall change to each path, so a file that was removed and written again is
+    /// only reported once.
+    fn emit_changes(&self) {
+        let dry_run = self.dry_run;
+
+        for command in &self.planned_commands {
+            output::emit(Event::CommandPlanned { command });
+        }
+
+        for path in &self.planned_dirs {
+            output::emit(Event::DirectoryCreated { path, dry_run });
+        }
+
+        for (path, contents) in &self.planned_files {
+            emit_file_change(path, path.exists(), contents.is_some(), dry_run);
+        }
+
+        let mut trees: Vec<&Path> = vec![];
+        let mut existed: BTreeMap<&Path, bool> = BTreeMap::new();
+
+        for change in &self.journal {
+            match change {
+                Change::CreatedTree(path) | Change::CreatedDir(path) => {
+                    output::emit(Event::DirectoryCreated { path, dry_run });
+
+                    if let Change::CreatedTree(_) = change {
+                        trees.push(path);
+                    }
+                }
+                Change::CreatedFile(path) => {
+                    existed.entry(path).or_insert(false);
+                }
+                Change::ReplacedFile(path, _) => {
+                    // Files in a tree made during this transaction are new, whatever happened to
+                    // them afterwards.
+                    let new = trees.iter().any(|tree| path.starts_with(tree));
+                    existed.entry(path).or_insert(!new);
+                }
+                // Removing the files inside is reported, which says all there is to say.
+                Change::RemovedDir(_) => {}
+            }
+        }
+
+        for (path, existed) in existed {
+            emit_file_change(path, existed, path.exists(), dry_run);
+        }
+    }
+
+    /// Describes a dry run's commands, new directories and file changes, with the file changes
+    /// given as a unified diff against what's on disk.
+    fn plan(&self) -> String {
+        let mut plan = String::new();
+
+        for command in &self.planned_commands {
+            plan.push_str(&format!("Would run: {}\n", command));
+        }
+
+        for dir in &self.planned_dirs {
+            plan.push_str(&format!("Would create directory {}\n", display_path(dir)));
+        }
+
+        for (path, contents) in &self.planned_files {
+            let original = fs::read(path).ok();
+            let label = display_path(path);
+
+            let (old_label, new_label) = match (&original, contents) {
+                // Written and then removed again, so there's nothing to show.
+                (None, None) => continue,
+                (None, Some(_)) => (diff::NO_FILE.to_string(), format!("b/{}", label)),
+                (Some(_), None) => (format!("a/{}", label), diff::NO_FILE.to_string()),
+                (Some(_), Some(_)) => (format!("a/{}", label), format!("b/{}", label)),
+            };
+
+            let old = original
+                .map(|contents| String::from_utf8_lossy(&contents).to_string())
+                .unwrap_or_default();
+            let new = contents
+                .as_ref()
+                .map(|contents| String::from_utf8_lossy(contents).to_string())
+                .unwrap_or_default();
+
+            plan.push_str(&diff::unified_diff(&old, &new, &old_label, &new_label));
+        }
+
+        if plan.is_empty() {
+            plan.push_str("Nothing to change\n");
+        }
+
+        plan
+    }
+
+    /// Undoes every change made so far, most recent first. Carries on past failures so as much as
+    /// possible is restored, and reports the first one.
+    pub fn roll_back(mut self) -> io::Result<()> {
+        self.undo()
+    }
+
+    fn undo(&mut self) -> io::Result<()> {
+        let mut result = Ok(());
+
+        while let Some(change) = self.journal.pop() {
+            let undone = match &change {
+                Change::CreatedFile(path) => fs::remove_file(path),
+                Change::CreatedDir(path) => fs::remove_dir(path),
+                Change::CreatedTree(path) => fs::remove_dir_all(path),
+                Change::ReplacedFile(path, original) => fs::write(path, original),
+                Change::RemovedDir(path) => fs::create_dir(path),
+            };
+
+            if result.is_ok() {
+                result = undone;
+            }
+        }
+
+        self.committed = true;
+
+        result
+    }
+}
+
+impl Drop for Transaction {
+    fn drop(&mut self) {
+        if !self.committed {
+            if let Err(e) = self.undo() {
+                output::note(&format!("Unable to fully roll back changes: {}", e));
+            }
+        }
+    }
+}
+
+fn emit_file_change(path: &Path, existed: bool, exists: bool, dry_run: bool) {
+    match (existed, exists) {
+        (false, true) => output::emit(Event::FileCreated { path, dry_run }),
+        (true, true) => output::emit(Event::FileUpdated { path, dry_run }),
+        (true, false) => output::emit(Event::FileRemoved { path, dry_run }),
+        (false, false) => {}
+    }
+}
+
+/// Shows paths relative to the current directory where possible, as they would be typed.
+fn display_path(path: &Path) -> String {
+    let relative = env::current_dir()
+        .ok()
+        .and_then(|current_dir| path.strip_prefix(current_dir).ok().map(Path::to_path_buf))
+        .unwrap_or_else(|| path.to_path_buf());
+
+    relative.display().to_string()
+}
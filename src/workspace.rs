@@ -0,0 +1,413 @@
+use crate::config::Config;
+use crate::dep;
+use crate::error::{io_error, GrumpyError};
+use crate::manifest::Manifest;
+use crate::names;
+use crate::project;
+use crate::targets::Layout;
+use crate::transaction::Transaction;
+use crate::{
+    create_binary_script, git_user_name, offer_roll_back, plan_cargo_new, requested_profiles,
+    CargoCommand,
+};
+use argh::FromArgs;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(FromArgs, PartialEq, Debug)]
+/// manage the members of a workspace
+#[argh(subcommand, name = "member")]
+pub struct MemberSubCommand {
+    /// path to the workspace's Cargo.toml
+    #[argh(option)]
+    manifest_path: Option<String>,
+
+    #[argh(subcommand)]
+    action: MemberAction,
+}
+
+#[derive(FromArgs, PartialEq, Debug)]
+#[argh(subcommand)]
+enum MemberAction {
+    Add(MemberAddAction),
+}
+
+#[derive(FromArgs, PartialEq, Debug)]
+/// create a member package that inherits the workspace's settings
+#[argh(subcommand, name = "add")]
+struct MemberAddAction {
+    /// name of the member
+    #[argh(positional)]
+    name: String,
+
+    /// create a library-only member
+    #[argh(switch)]
+    lib: bool,
+
+    /// create a binary-only member
+    #[argh(switch)]
+    bin: bool,
+
+    #[argh(option, short = 's')]
+    /// what to call the executable script, defaults to new.script-name from config
+    script_name: Option<String>,
+
+    #[argh(option, short = 't')]
+    /// template to generate the executable script from, defaults to templates.default from config
+    template: Option<String>,
+
+    #[argh(option, short = 'd')]
+    /// dependency profiles to add, defaults to dependencies.bin-profiles and/or
+    /// dependencies.lib-profiles from config
+    deps: Vec<String>,
+}
+
+/// A package in a workspace.
+pub struct Member {
+    /// The package name.
+    pub name: String,
+    pub root: PathBuf,
+    /// The member's directory relative to the workspace root, with `/` between components.
+    pub path: String,
+}
+
+/// The workspace's members, as listed in `workspace.members` less those in `workspace.exclude`.
+/// Globs are expanded as cargo does, with `*` and `?` matching within a single directory name.
+/// A workspace root that's a package itself is a member too, with a path of `.`.
+pub fn members(
+    transaction: &Transaction,
+    workspace_root: &Path,
+) -> Result<Vec<Member>, GrumpyError> {
+    let workspace = Manifest::load(transaction, workspace_root)?;
+    let excluded = workspace.workspace_paths("exclude");
+
+    let mut paths = vec![];
+
+    if workspace.package_name().is_some() {
+        paths.push(".".to_string());
+    }
+
+    for pattern in workspace.workspace_paths("members") {
+        for path in expand_member_glob(workspace_root, &pattern)? {
+            if !paths.contains(&path) && !excluded.iter().any(|exclude| same_path(exclude, &path)) {
+                paths.push(path);
+            }
+        }
+    }
+
+    let mut members = vec![];
+
+    for path in paths {
+        let root = workspace_root.join(&path);
+
+        if !transaction.exists(&root.join("Cargo.toml")) {
+            continue;
+        }
+
+        if let Some(name) = Manifest::load(transaction, &root)?.package_name() {
+            members.push(Member {
+                name: name.to_string(),
+                root,
+                path,
+            });
+        }
+    }
+
+    Ok(members)
+}
+
+/// The directories a `workspace.members` entry refers to, relative to the workspace root.
+fn expand_member_glob(workspace_root: &Path, pattern: &str) -> Result<Vec<String>, GrumpyError> {
+    let mut paths = vec![String::new()];
+
+    for component in pattern.split('/').filter(|c| !c.is_empty() && *c != ".") {
+        let mut expanded = vec![];
+
+        for path in paths {
+            let join = |name: &str| {
+                if path.is_empty() {
+                    name.to_string()
+                } else {
+                    format!("{}/{}", path, name)
+                }
+            };
+
+            if !component.contains(['*', '?']) {
+                expanded.push(join(component));
+                continue;
+            }
+
+            let dir = workspace_root.join(&path);
+
+            let entries = match fs::read_dir(&dir) {
+                Ok(entries) => entries,
+                Err(_) => continue,
+            };
+
+            let mut names = vec![];
+
+            for entry in entries {
+                let entry = entry.map_err(io_error("read", &dir))?;
+
+                if entry.path().is_dir() {
+                    let name = entry.file_name().to_string_lossy().to_string();
+
+                    if glob_matches(component, &name) {
+                        names.push(join(&name));
+                    }
+                }
+            }
+
+            names.sort();
+            expanded.extend(names);
+        }
+
+        paths = expanded;
+    }
+
+    Ok(paths.into_iter().filter(|path| !path.is_empty()).collect())
+}
+
+/// Matches a name against a glob of `*` and `?` wildcards.
+fn glob_matches(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+
+    // Where to resume after the last `*`, should the rest of the pattern fail to match.
+    let mut star = None;
+    let (mut p, mut n) = (0, 0);
+
+    while n < name.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == name[n]) {
+            p += 1;
+            n += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, n));
+            p += 1;
+        } else if let Some((star_p, star_n)) = star {
+            p = star_p + 1;
+            n = star_n + 1;
+            star = Some((star_p, star_n + 1));
+        } else {
+            return false;
+        }
+    }
+
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+/// Whether two paths from the workspace manifest name the same directory.
+fn same_path(a: &str, b: &str) -> bool {
+    fn components(path: &str) -> impl Iterator<Item = &str> {
+        path.split('/').filter(|c| !c.is_empty() && *c != ".")
+    }
+
+    components(a).eq(components(b))
+}
+
+/// Writes the manifest of a virtual workspace, with shared package metadata and an empty
+/// `[workspace.dependencies]` table for members to inherit from.
+pub fn create_workspace(
+    transaction: &mut Transaction,
+    workspace_root: &Path,
+    edition: Option<&str>,
+) -> Result<(), GrumpyError> {
+    let mut manifest = String::from("[workspace]\nresolver = \"2\"\nmembers = []\n\n");
+
+    manifest.push_str("[workspace.package]\nversion = \"0.1.0\"\n");
+    manifest.push_str(&format!("edition = \"{}\"\n", edition.unwrap_or("2021")));
+
+    if let Some(author) = git_user_name().filter(|author| !author.is_empty()) {
+        manifest.push_str(&format!(
+            "authors = [{}]\n",
+            toml_edit::Value::from(author.as_str())
+        ));
+    }
+
+    manifest.push_str("\n[workspace.dependencies]\n");
+
+    let manifest_path = workspace_root.join("Cargo.toml");
+    let gitignore_path = workspace_root.join(".gitignore");
+
+    transaction
+        .write(&manifest_path, manifest.as_bytes())
+        .map_err(io_error("write", &manifest_path))?;
+    transaction
+        .write(&gitignore_path, b"/target\n")
+        .map_err(io_error("write", &gitignore_path))
+}
+
+/// Makes a freshly created member part of the workspace: listed in `workspace.members`, and
+/// inheriting every field the workspace shares under `[workspace.package]`.
+fn join_workspace(
+    transaction: &mut Transaction,
+    workspace_root: &Path,
+    member_root: &Path,
+    member_path: &str,
+) -> Result<(), GrumpyError> {
+    let mut workspace = Manifest::load(transaction, workspace_root)?;
+
+    // Recent versions of cargo add the member themselves.
+    if workspace.add_workspace_member(member_path)? {
+        workspace.save(transaction)?;
+    }
+
+    let mut member = Manifest::load(transaction, member_root)?;
+
+    for key in workspace.workspace_package_keys() {
+        member.inherit_package_field(&key)?;
+    }
+
+    member.save(transaction)
+}
+
+fn add_member(
+    add_args: &MemberAddAction,
+    workspace_root: &Path,
+    dry_run: bool,
+) -> Result<(), GrumpyError> {
+    if add_args.bin && add_args.lib {
+        return Err(GrumpyError::ConflictingOptions("--bin", "--lib"));
+    }
+
+    // Check names before cargo gets to them, as its errors don't say what would work instead.
+    names::validate_project_name(&add_args.name)?;
+
+    if let Some(script_name) = &add_args.script_name {
+        names::script_name(script_name)?;
+    }
+
+    let member_root = workspace_root.join(&add_args.name);
+
+    if member_root.exists() {
+        return Err(GrumpyError::AlreadyExists { path: member_root });
+    }
+
+    let mut cargo_command = CargoCommand::new("new");
+
+    if add_args.bin {
+        cargo_command.add_arg("--bin");
+    } else {
+        cargo_command.add_arg("--lib");
+    }
+
+    // The workspace is under version control as a whole, if at all.
+    cargo_command
+        .add_arg("--vcs")
+        .add_arg("none")
+        .add_arg(&member_root.to_string_lossy());
+
+    let mut transaction = Transaction::new(dry_run);
+
+    if dry_run {
+        transaction.plan_command(&cargo_command.description());
+
+        plan_cargo_new(
+            &mut transaction,
+            &member_root,
+            &add_args.name,
+            None,
+            !add_args.bin,
+        )
+        .map_err(io_error("create", &member_root))?;
+    } else {
+        let manifest_path = workspace_root.join("Cargo.toml");
+        let original = fs::read(&manifest_path).map_err(io_error("read", &manifest_path))?;
+
+        cargo_command.run()?;
+
+        // From here on, any failure removes the member again, and undoes cargo adding it to the
+        // workspace.
+        transaction.track_created_tree(&member_root);
+
+        if !fs::read(&manifest_path).is_ok_and(|contents| contents == original) {
+            transaction.track_written_file(&manifest_path, Some(original));
+        }
+    }
+
+    join_workspace(
+        &mut transaction,
+        workspace_root,
+        &member_root,
+        &add_args.name,
+    )?;
+
+    let config = Config::load(Some(&member_root), Some(workspace_root))?;
+
+    // A default member has both a library and a binary, so it gets both sets of profiles.
+    let default_profile_keys: &[&str] = if add_args.bin {
+        &["dependencies.bin-profiles"]
+    } else if add_args.lib {
+        &["dependencies.lib-profiles"]
+    } else {
+        &["dependencies.lib-profiles", "dependencies.bin-profiles"]
+    };
+
+    let dependencies = config.profile_dependencies(&requested_profiles(
+        &config,
+        &add_args.deps,
+        default_profile_keys,
+    ))?;
+
+    if !add_args.lib {
+        let script_name = names::script_name(match &add_args.script_name {
+            Some(script_name) => script_name.as_str(),
+            None => config.get_str("new.script-name").unwrap_or("main.rs"),
+        })?;
+
+        create_binary_script(
+            &mut transaction,
+            &config,
+            &member_root,
+            &script_name,
+            add_args.template.as_ref(),
+            Layout::File,
+            true,
+        )?;
+    }
+
+    if let Err(error) = dep::add_dependencies(
+        &mut transaction,
+        &member_root,
+        Some(workspace_root),
+        &dependencies,
+    ) {
+        offer_roll_back(transaction);
+        return Err(error);
+    }
+
+    transaction.commit();
+
+    Ok(())
+}
+
+pub fn process_member(member_args: &MemberSubCommand, dry_run: bool) -> Result<(), GrumpyError> {
+    let workspace_root = project::locate_workspace(member_args.manifest_path.as_ref())?;
+
+    match &member_args.action {
+        MemberAction::Add(add_args) => add_member(add_args, &workspace_root, dry_run),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn globs_match_like_cargo() {
+        assert!(glob_matches("*", "core"));
+        assert!(glob_matches("crate-*", "crate-core"));
+        assert!(glob_matches("crate-?", "crate-a"));
+        assert!(glob_matches("*-cli", "tool-cli"));
+        assert!(glob_matches("a*b*c", "aXXbYYc"));
+        assert!(!glob_matches("crate-?", "crate-ab"));
+        assert!(!glob_matches("*-cli", "tool-cli-old"));
+        assert!(!glob_matches("core", "core2"));
+    }
+
+    #[test]
+    fn paths_compare_by_component() {
+        assert!(same_path("crates/core", "./crates//core/"));
+        assert!(!same_path("crates/core", "crates/core2"));
+    }
+}